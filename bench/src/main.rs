@@ -4,13 +4,13 @@ use hist_axes::uniform::Uniform;
 use hist_dense::vechist::VecHist;
 use hist_sparse::hashmaphist::HashMapHist;
 use hist_sparse::sparsehist::SparseHist;
-use hist_storages::StorageType;
+use hist_storages::{Storage, StorageType};
 
 use rand::{thread_rng, Rng};
 use std::time::Instant;
 
 fn fill_n(
-    hist: &mut dyn Histogram,
+    hist: &mut dyn Histogram<Storage = Storage>,
     ax1: &Uniform,
     ax2: &Uniform,
     ax1_entries: &Vec<f32>,
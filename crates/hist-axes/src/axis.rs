@@ -1,7 +1,34 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use thiserror::Error;
 
-pub trait Axis: Debug {
+use crate::bin::{Bin, Value};
+use crate::metadata::Metadata;
+
+// Allows cloning of `Box<dyn Axis>`, implemented for all `Clone` axes
+pub trait AxisClone {
+    fn clone_box(&self) -> Box<dyn Axis>;
+}
+
+impl<T> AxisClone for T
+where
+    T: 'static + Axis + Clone,
+{
+    fn clone_box(&self) -> Box<dyn Axis> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Axis> {
+    fn clone(&self) -> Box<dyn Axis> {
+        self.clone_box()
+    }
+}
+
+pub trait Axis: Debug + AxisClone {
+    // type name of the axis, e.g. "Uniform"
+    fn kind(&self) -> &'static str;
+
     // bin layout: [bins, underflow, overflow]
     fn num_bins(&self, flow: bool) -> usize;
     fn underflow(&self) -> usize {
@@ -10,6 +37,234 @@ pub trait Axis: Debug {
     fn overflow(&self) -> usize {
         self.num_bins(true) - 1
     }
+
+    // bin at `index` (as returned by the axis' `index` method)
+    // returns `None` for flow bins and out-of-range indices
+    fn bin(&self, index: usize) -> Option<Bin>;
+
+    // label of a categorical bin, `None` for continuous axes
+    fn value(&self, index: usize) -> Option<Value> {
+        match self.bin(index)? {
+            Bin::SingleValue(bin) => Some(bin.value),
+            Bin::Interval(_) => None,
+        }
+    }
+
+    // all regular bins (no flow bins) in axis order
+    fn bins(&self) -> Vec<Bin> {
+        (0..self.num_bins(true))
+            .filter_map(|index| self.bin(index))
+            .collect()
+    }
+
+    // bin indices in plotting order: [underflow, bins, overflow] with `flow`,
+    // only the regular bins otherwise (categorical axes have no underflow bin)
+    fn display_order(&self, flow: bool) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.num_bins(true))
+            .filter(|&index| self.bin(index).is_some())
+            .collect();
+        if flow {
+            if self.underflow() != self.overflow() {
+                order.insert(0, self.underflow());
+            }
+            order.push(self.overflow());
+        }
+        order
+    }
+
+    // bin edges of the regular bins
+    // categorical axes have unit-width bins: [0, 1, ..., num]
+    fn edges(&self) -> Vec<f64> {
+        let bins = self.bins();
+        let mut edges = Vec::with_capacity(bins.len() + 1);
+        for (i, bin) in bins.iter().enumerate() {
+            match bin {
+                Bin::Interval(interval) => {
+                    if i == 0 {
+                        edges.push(interval.low);
+                    }
+                    edges.push(interval.high);
+                }
+                Bin::SingleValue(_) => {
+                    if i == 0 {
+                        edges.push(0.0);
+                    }
+                    edges.push((i + 1) as f64);
+                }
+            }
+        }
+        edges
+    }
+
+    fn centers(&self) -> Vec<f64> {
+        self.edges()
+            .windows(2)
+            .map(|w| (w[0] + w[1]) / 2.0)
+            .collect()
+    }
+
+    fn widths(&self) -> Vec<f64> {
+        self.edges().windows(2).map(|w| w[1] - w[0]).collect()
+    }
+
+    // structural equality with another axis: same type, name and bins
+    // (labels and attributes are ignored, the axes' `PartialEq` follows this)
+    fn check_equal(&self, other: &dyn Axis) -> Result<(), AxisMismatch> {
+        check_header(self, other)?;
+        let edges = self.edges();
+        for (index, (left, right)) in edges.iter().zip(other.edges()).enumerate() {
+            if *left != right {
+                return Err(AxisMismatch::Edge {
+                    index,
+                    left: *left,
+                    right,
+                });
+            }
+        }
+        let labels = self.bins().into_iter().map(bin_label);
+        for (index, (left, right)) in labels
+            .zip(other.bins().into_iter().map(bin_label))
+            .enumerate()
+        {
+            if left != right {
+                return Err(AxisMismatch::Label { index, left, right });
+            }
+        }
+        Ok(())
+    }
+
+    // like `check_equal`, but categorical axes may list their bins in a different order
+    fn check_compatible(&self, other: &dyn Axis) -> Result<(), AxisMismatch> {
+        let labels: Vec<Value> = self.bins().into_iter().filter_map(bin_label).collect();
+        if labels.is_empty() {
+            return self.check_equal(other);
+        }
+        check_header(self, other)?;
+        let other_labels: Vec<Value> = other.bins().into_iter().filter_map(bin_label).collect();
+        let set: HashSet<&Value> = labels.iter().collect();
+        let other_set: HashSet<&Value> = other_labels.iter().collect();
+        if set != other_set {
+            return Err(AxisMismatch::Categories {
+                missing: labels
+                    .iter()
+                    .filter(|v| !other_set.contains(v))
+                    .cloned()
+                    .collect(),
+                extra: other_labels
+                    .iter()
+                    .filter(|v| !set.contains(v))
+                    .cloned()
+                    .collect(),
+            });
+        }
+        Ok(())
+    }
+
+    // maps each index of a compatible `other` axis (including flow bins) to the index of this axis
+    fn index_map(&self, other: &dyn Axis) -> Result<Vec<usize>, AxisMismatch> {
+        self.check_compatible(other)?;
+        let lookup: HashMap<Value, usize> = (0..self.num_bins(true))
+            .filter_map(|idx| self.value(idx).map(|value| (value, idx)))
+            .collect();
+        let map = (0..other.num_bins(true))
+            .map(|idx| match other.value(idx) {
+                Some(value) => lookup[&value],
+                None if idx == other.overflow() => self.overflow(),
+                None if idx == other.underflow() => self.underflow(),
+                None => idx,
+            })
+            .collect();
+        Ok(map)
+    }
+
+    fn metadata(&self) -> &Metadata;
+
+    fn metadata_mut(&mut self) -> &mut Metadata;
+
+    fn name(&self) -> Option<&str> {
+        self.metadata().name.as_deref()
+    }
+
+    // falls back to the name if no label is set
+    fn label(&self) -> Option<&str> {
+        self.metadata().label.as_deref().or(self.name())
+    }
+
+    fn with_name(mut self, name: &str) -> Self
+    where
+        Self: Sized,
+    {
+        self.metadata_mut().name = Some(name.to_string());
+        self
+    }
+
+    fn with_label(mut self, label: &str) -> Self
+    where
+        Self: Sized,
+    {
+        self.metadata_mut().label = Some(label.to_string());
+        self
+    }
+
+    fn with_attr(mut self, key: &str, value: &str) -> Self
+    where
+        Self: Sized,
+    {
+        self.metadata_mut()
+            .attrs
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+fn check_header<A: Axis + ?Sized>(axis: &A, other: &dyn Axis) -> Result<(), AxisMismatch> {
+    if axis.kind() != other.kind() {
+        return Err(AxisMismatch::Kind(axis.kind(), other.kind()));
+    }
+    if axis.name() != other.name() {
+        return Err(AxisMismatch::Name(
+            axis.name().map(str::to_string),
+            other.name().map(str::to_string),
+        ));
+    }
+    if axis.num_bins(false) != other.num_bins(false) {
+        return Err(AxisMismatch::NumBins(
+            axis.num_bins(false),
+            other.num_bins(false),
+        ));
+    }
+    Ok(())
+}
+
+fn bin_label(bin: Bin) -> Option<Value> {
+    match bin {
+        Bin::SingleValue(bin) => Some(bin.value),
+        Bin::Interval(_) => None,
+    }
+}
+
+// Describes how two axes differ
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AxisMismatch {
+    #[error("axis types differ ({0} vs {1})")]
+    Kind(&'static str, &'static str),
+    #[error("axis names differ ({0:?} vs {1:?})")]
+    Name(Option<String>, Option<String>),
+    #[error("number of bins differs ({0} vs {1})")]
+    NumBins(usize, usize),
+    #[error("bin edge {index} differs ({left} vs {right})")]
+    Edge { index: usize, left: f64, right: f64 },
+    #[error("label of bin {index} differs ({left:?} vs {right:?})")]
+    Label {
+        index: usize,
+        left: Option<Value>,
+        right: Option<Value>,
+    },
+    #[error("categories differ (missing: {missing:?}, extra: {extra:?})")]
+    Categories {
+        missing: Vec<Value>,
+        extra: Vec<Value>,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -26,4 +281,24 @@ pub enum AxisError {
     FailedToFindBinIndex,
     #[error("invalid value type for axis, can't be used for indexing.")]
     InvalidValueType,
+    #[error("sample must be non-empty and contain only finite values.")]
+    InvalidSample,
+    #[error("log-spaced edges need a positive, increasing range.")]
+    InvalidLogRange,
+    #[error("expected a continuous axis with interval bins.")]
+    ExpectedContinuousAxis,
+    #[error("number of bin contents ({ncontents}) does not match number of bins ({nbins}).")]
+    BinContentMismatch { nbins: usize, ncontents: usize },
+    #[error("bin groups must be non-empty and cover all {nbins} bins.")]
+    InvalidBinGroups { nbins: usize },
+    #[error("target relative uncertainty should be positive.")]
+    InvalidUncertaintyTarget,
+    #[error("duplicate category label '{0}'.")]
+    DuplicateLabel(String),
+    #[error("interval high edge must be greater than the low edge.")]
+    InvalidInterval,
+    #[error("category label '{0}' is reserved.")]
+    ReservedLabel(String),
+    #[error("time is more than 2^63 nanoseconds (about 292 years) away from the unix epoch.")]
+    TimestampOutOfRange,
 }
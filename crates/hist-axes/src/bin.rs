@@ -1,6 +1,8 @@
+use crate::axis::AxisError;
 use num_traits::{Num, NumCast, NumOps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Interval<V> {
     pub low: V,
     pub high: V,
@@ -10,9 +12,17 @@ impl<V> Interval<V>
 where
     V: PartialOrd + Num + NumCast + NumOps + Copy,
 {
+    // panics if `high` is not greater than `low`, see `try_new`
     pub fn new(low: V, high: V) -> Self {
-        assert!(high > low, "high must be greater than low");
-        Self { low, high }
+        Self::try_new(low, high).expect("high must be greater than low")
+    }
+
+    pub fn try_new(low: V, high: V) -> Result<Self, AxisError> {
+        if high > low {
+            Ok(Self { low, high })
+        } else {
+            Err(AxisError::InvalidInterval)
+        }
     }
 
     pub fn center(&self) -> V {
@@ -24,7 +34,7 @@ where
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SingleValue<V> {
     pub value: V,
 }
@@ -34,3 +44,36 @@ impl<V> SingleValue<V> {
         Self { value }
     }
 }
+
+// Label of a categorical bin
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Value {
+    Int(i32),
+    Str(String),
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+// Type-erased bin as returned by `Axis::bin`
+// continuous axes return intervals, categorical axes single values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bin {
+    Interval(Interval<f64>),
+    SingleValue(SingleValue<Value>),
+}
@@ -1,14 +1,23 @@
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 
 use crate::axis::{Axis, AxisError};
-use crate::bin::SingleValue;
+use crate::bin::{Bin, SingleValue, Value};
+use crate::metadata::Metadata;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+// Categorical axis with string labels
+// labels are stored once (shared between the ordered list and the lookup table)
+// and can be looked up by `&str` without allocating
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "CategoryRepr", try_from = "CategoryRepr")]
 pub struct Category {
-    pub idx2bin: HashMap<usize, SingleValue<String>>,
-    pub bin2idx: HashMap<String, usize>,
+    // labels in insertion order, the label of bin `i` is `labels[i - 1]`
+    labels: Vec<Arc<str>>,
+    lookup: HashMap<Arc<str>, usize>,
+    pub metadata: Metadata,
 }
 
 impl Category {
@@ -25,32 +34,93 @@ impl Category {
 
         let num = bins.len();
 
-        let mut idx2bin = HashMap::with_capacity(num + 1);
-        let mut bin2idx = HashMap::with_capacity(num + 1);
-        // under-/overflow bin
-        idx2bin.insert(0, SingleValue::new(Self::OVERFLOW.to_string()));
-        bin2idx.insert(Self::OVERFLOW.to_string(), 0);
-        for (idx, bin) in bins.iter().enumerate() {
+        let mut labels = Vec::with_capacity(num);
+        let mut lookup = HashMap::with_capacity(num);
+        for (idx, bin) in bins.into_iter().enumerate() {
             // check for reserved bin name
-            assert!(bin != &Self::OVERFLOW.to_string());
-            idx2bin.insert(idx + 1, SingleValue::new(bin.clone()));
-            bin2idx.insert(bin.clone(), idx + 1);
+            if bin == Self::OVERFLOW {
+                return Err(AxisError::ReservedLabel(bin).into());
+            }
+            let label: Arc<str> = Arc::from(bin);
+            if lookup.insert(label.clone(), idx + 1).is_some() {
+                return Err(AxisError::DuplicateLabel(label.to_string()).into());
+            }
+            labels.push(label);
         }
 
-        Ok(Self { idx2bin, bin2idx })
+        Ok(Self {
+            labels,
+            lookup,
+            metadata: Metadata::default(),
+        })
     }
 
-    pub fn index(&self, value: String) -> usize {
-        match self.bin2idx.get(&value.to_string()) {
+    pub fn index(&self, value: &str) -> usize {
+        match self.lookup.get(value) {
             Some(&index) => index,
             None => self.overflow(),
         }
     }
+
+    // pre-interned label id: the position of `value` in the list of labels
+    // `index_id(id)` is equivalent to `index(value)` but skips the hash lookup
+    pub fn id(&self, value: &str) -> Option<usize> {
+        self.lookup.get(value).map(|&index| index - 1)
+    }
+
+    pub fn index_id(&self, id: usize) -> usize {
+        if id < self.labels.len() {
+            id + 1
+        } else {
+            self.overflow()
+        }
+    }
+
+    // labels in insertion order
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.labels.iter().map(|label| label.as_ref())
+    }
+}
+
+// serialized form of `Category`, the lookup table is rebuilt on deserialization
+#[derive(Serialize, Deserialize)]
+struct CategoryRepr {
+    labels: Vec<String>,
+    metadata: Metadata,
+}
+
+impl From<Category> for CategoryRepr {
+    fn from(axis: Category) -> Self {
+        Self {
+            labels: axis.labels().map(str::to_string).collect(),
+            metadata: axis.metadata,
+        }
+    }
+}
+
+impl TryFrom<CategoryRepr> for Category {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: CategoryRepr) -> Result<Self> {
+        let mut axis = Category::new(repr.labels)?;
+        axis.metadata = repr.metadata;
+        Ok(axis)
+    }
+}
+
+impl PartialEq for Category {
+    fn eq(&self, other: &Self) -> bool {
+        self.check_equal(other).is_ok()
+    }
 }
 
 impl Axis for Category {
+    fn kind(&self) -> &'static str {
+        "Category"
+    }
+
     fn num_bins(&self, flow: bool) -> usize {
-        let num = self.idx2bin.len() - 1;
+        let num = self.labels.len();
         if flow {
             // include overflow bin
             // there is no underflow bin for integer axis
@@ -67,22 +137,33 @@ impl Axis for Category {
     fn overflow(&self) -> usize {
         0
     }
+
+    fn bin(&self, index: usize) -> Option<Bin> {
+        if index == self.overflow() {
+            return None;
+        }
+        self.labels
+            .get(index - 1)
+            .map(|label| Bin::SingleValue(SingleValue::new(Value::Str(label.to_string()))))
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
 }
 
 impl Debug for Category {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let names: Vec<String> = self
-            .idx2bin
-            .values()
-            .map(|bin| bin.value.clone())
-            .filter(|v| v != &Category::OVERFLOW.to_string())
-            .collect();
-        write!(
-            f,
-            "Category({:?}, #{:#?} bins)",
-            names,
-            self.num_bins(false),
-        )
+        let names: Vec<&str> = self.labels().collect();
+        write!(f, "Category({:?}, #{:#?} bins", names, self.num_bins(false))?;
+        if let Some(name) = self.name() {
+            write!(f, ", name={:?}", name)?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -101,10 +182,81 @@ mod tests {
         .unwrap();
         assert_eq!(axis.num_bins(false), 3);
         assert_eq!(axis.num_bins(true), 4);
-        assert_eq!(axis.index("foo".to_string()), 1);
-        assert_eq!(axis.index("bar".to_string()), 2);
-        assert_eq!(axis.index("baz".to_string()), 3);
+        assert_eq!(axis.index("foo"), 1);
+        assert_eq!(axis.index("bar"), 2);
+        assert_eq!(axis.index("baz"), 3);
         // overflow
-        assert_eq!(axis.index(Category::OVERFLOW.to_string()), 0);
+        assert_eq!(axis.index(Category::OVERFLOW), 0);
+    }
+
+    #[test]
+    fn test_category_axis_lookup() {
+        let labels: Vec<String> = ["z", "y", "x", "w"].iter().map(|l| l.to_string()).collect();
+        let axis = Category::new(labels.clone()).unwrap();
+        // insertion order is kept
+        assert_eq!(
+            axis.labels().collect::<Vec<&str>>(),
+            vec!["z", "y", "x", "w"]
+        );
+        assert_eq!(
+            format!("{:?}", axis),
+            "Category([\"z\", \"y\", \"x\", \"w\"], #4 bins)"
+        );
+        // pre-interned label ids
+        let id = axis.id("x").unwrap();
+        assert_eq!(id, 2);
+        assert_eq!(axis.index_id(id), axis.index("x"));
+        assert_eq!(axis.index_id(10), axis.overflow());
+        assert_eq!(axis.id("unknown"), None);
+
+        let mut duplicated = labels;
+        duplicated.push("y".to_string());
+        assert!(Category::new(duplicated).is_err());
+    }
+
+    #[test]
+    fn test_category_axis_introspection() {
+        let axis = Category::new(vec!["foo".to_string(), "bar".to_string()]).unwrap();
+        assert!(Category::new(vec!["__overflow__".to_string()]).is_err());
+        assert_eq!(
+            axis.value(axis.index("bar")),
+            Some(Value::Str("bar".to_string()))
+        );
+        assert_eq!(axis.value(axis.overflow()), None);
+        assert_eq!(axis.centers(), vec![0.5, 1.5]);
+        let labels: Vec<Value> = axis
+            .bins()
+            .into_iter()
+            .map(|bin| match bin {
+                Bin::SingleValue(bin) => bin.value,
+                Bin::Interval(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            vec![Value::Str("foo".to_string()), Value::Str("bar".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_category_axis_compatibility() {
+        use crate::axis::AxisMismatch;
+
+        let labels = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let axis = Category::new(labels(&["a", "b", "c"])).unwrap();
+        let reordered = Category::new(labels(&["c", "a", "b"])).unwrap();
+        assert!(axis.check_equal(&reordered).is_err());
+        assert!(axis.check_compatible(&reordered).is_ok());
+        // maps indices of `reordered` to indices of `axis`
+        assert_eq!(axis.index_map(&reordered).unwrap(), vec![0, 3, 1, 2]);
+
+        let other = Category::new(labels(&["a", "b", "d"])).unwrap();
+        assert_eq!(
+            axis.check_compatible(&other),
+            Err(AxisMismatch::Categories {
+                missing: vec![Value::Str("c".to_string())],
+                extra: vec![Value::Str("d".to_string())],
+            })
+        );
     }
 }
@@ -0,0 +1,307 @@
+use std::fmt::{Debug, Formatter};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::axis::{Axis, AxisError};
+use crate::bin::{Bin, Interval};
+use crate::metadata::Metadata;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const NANOS_PER_DAY: i64 = 86_400 * NANOS_PER_SEC;
+
+// Calendar-aware bin widths, bin edges are aligned to UTC midnight
+// (and to the first day of the month/year respectively)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Calendar {
+    Day,
+    Month,
+    Year,
+}
+
+// Time axis, values are UTC timestamps in nanoseconds since the unix epoch
+// bins are half-open intervals [low, high)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DateTime {
+    pub bins: Vec<Interval<i64>>,
+    pub metadata: Metadata,
+}
+
+impl DateTime {
+    // arbitrary bin edges (like `Variable`)
+    pub fn new(edges: Vec<i64>) -> Result<Self> {
+        if edges.len() < 2 {
+            return Err(AxisError::InvalidNumberOfBinEdges.into());
+        }
+        if !edges.windows(2).all(|w| w[0] < w[1]) {
+            return Err(AxisError::FailedToSortBins.into());
+        }
+        let bins = edges
+            .windows(2)
+            .map(|w| Interval::new(w[0], w[1]))
+            .collect();
+        Ok(Self {
+            bins,
+            metadata: Metadata::default(),
+        })
+    }
+
+    // `num` bins of fixed duration `step` (like `Uniform`)
+    pub fn uniform(num: usize, start: i64, step: Duration) -> Result<Self> {
+        if num == 0 {
+            return Err(AxisError::InvalidNumberOfBins.into());
+        }
+        let step = i64::try_from(step.as_nanos()).map_err(|_| AxisError::InvalidStepSize)?;
+        if step <= 0 {
+            return Err(AxisError::InvalidStepSize.into());
+        }
+        let edges = (0..=num as i64)
+            .map(|i| {
+                i.checked_mul(step)
+                    .and_then(|offset| start.checked_add(offset))
+            })
+            .collect::<Option<Vec<i64>>>()
+            .ok_or(AxisError::InvalidNumberOfBins)?;
+        Self::new(edges)
+    }
+
+    // bins of `step` calendar units covering [start, stop)
+    // the first edge is `start` rounded down to the calendar unit
+    pub fn calendar(start: i64, stop: i64, unit: Calendar, step: u32) -> Result<Self> {
+        if step == 0 {
+            return Err(AxisError::InvalidStepSize.into());
+        }
+        if stop <= start {
+            return Err(AxisError::InvalidNumberOfBins.into());
+        }
+        let (year, month, day) = civil_from_days(start.div_euclid(NANOS_PER_DAY));
+        let (mut year, mut month, day) = match unit {
+            Calendar::Day => (year, month, day),
+            Calendar::Month => (year, month, 1),
+            Calendar::Year => (year, 1, 1),
+        };
+        let mut days = days_from_civil(year, month, day);
+        let step = step as i64;
+        let mut edges = vec![nanos_from_days(days)?];
+        while *edges.last().unwrap() < stop {
+            match unit {
+                Calendar::Day => {
+                    days = days
+                        .checked_add(step)
+                        .ok_or(AxisError::TimestampOutOfRange)?
+                }
+                Calendar::Month => {
+                    let months = year
+                        .checked_mul(12)
+                        .and_then(|months| months.checked_add(month as i64 - 1 + step))
+                        .ok_or(AxisError::TimestampOutOfRange)?;
+                    year = months.div_euclid(12);
+                    month = months.rem_euclid(12) as u32 + 1;
+                    days = days_from_civil(year, month, 1);
+                }
+                Calendar::Year => {
+                    year = year
+                        .checked_add(step)
+                        .ok_or(AxisError::TimestampOutOfRange)?;
+                    days = days_from_civil(year, 1, 1);
+                }
+            }
+            edges.push(nanos_from_days(days)?);
+        }
+        Self::new(edges)
+    }
+
+    pub fn index(&self, value: i64) -> usize {
+        // bin layout: [bins, underflow, overflow]
+        if value < self.bins[0].low {
+            self.underflow()
+        } else if value >= self.bins[self.bins.len() - 1].high {
+            self.overflow()
+        } else {
+            self.bins.partition_point(|bin| bin.high <= value)
+        }
+    }
+
+    pub fn index_time(&self, time: SystemTime) -> Result<usize> {
+        Ok(self.index(timestamp(time)?))
+    }
+
+    // ISO 8601 representation of the bin edges
+    pub fn edge_labels(&self) -> Vec<String> {
+        let mut edges: Vec<i64> = self.bins.iter().map(|bin| bin.low).collect();
+        edges.push(self.bins[self.bins.len() - 1].high);
+        edges.into_iter().map(format_timestamp).collect()
+    }
+}
+
+impl PartialEq for DateTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.check_equal(other).is_ok()
+    }
+}
+
+impl Axis for DateTime {
+    fn kind(&self) -> &'static str {
+        "DateTime"
+    }
+
+    fn num_bins(&self, flow: bool) -> usize {
+        if flow {
+            // include underflow and overflow bins
+            return self.bins.len() + 2;
+        }
+        self.bins.len()
+    }
+
+    fn bin(&self, index: usize) -> Option<Bin> {
+        self.bins
+            .get(index)
+            .map(|bin| Bin::Interval(Interval::new(bin.low as f64, bin.high as f64)))
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+}
+
+impl Debug for DateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let labels = self.edge_labels();
+        write!(
+            f,
+            "DateTime({}..{}, #{:#?} bins",
+            labels[0],
+            labels[labels.len() - 1],
+            self.num_bins(false),
+        )?;
+        if let Some(name) = self.name() {
+            write!(f, ", name={:?}", name)?;
+        }
+        write!(f, ")")
+    }
+}
+
+// nanoseconds since the unix epoch, negative before 1970
+pub fn timestamp(time: SystemTime) -> Result<i64> {
+    let nanos = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_nanos()),
+        Err(before) => i64::try_from(before.duration().as_nanos()).map(|nanos| -nanos),
+    };
+    Ok(nanos.map_err(|_| AxisError::TimestampOutOfRange)?)
+}
+
+// start of the day `days` since the unix epoch in nanoseconds
+fn nanos_from_days(days: i64) -> Result<i64, AxisError> {
+    days.checked_mul(NANOS_PER_DAY)
+        .ok_or(AxisError::TimestampOutOfRange)
+}
+
+// e.g. "2024-02-29T12:00:00Z", sub-second precision is only shown if non-zero
+pub fn format_timestamp(value: i64) -> String {
+    let days = value.div_euclid(NANOS_PER_DAY);
+    let nanos = value.rem_euclid(NANOS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let secs = nanos / NANOS_PER_SEC;
+    let subsec = nanos % NANOS_PER_SEC;
+    let mut out = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    );
+    if subsec != 0 {
+        out.push_str(&format!(".{:09}", subsec));
+    }
+    out.push('Z');
+    out
+}
+
+// days since 1970-01-01 for a proleptic Gregorian date
+// (http://howardhinnant.github.io/date_algorithms.html)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01T00:00:00Z
+    const NEW_YEAR_2024: i64 = 1_704_067_200 * NANOS_PER_SEC;
+
+    #[test]
+    fn test_datetime_axis() {
+        let hour = Duration::from_secs(3600);
+        let axis = DateTime::uniform(24, NEW_YEAR_2024, hour).unwrap();
+        assert_eq!(axis.num_bins(false), 24);
+        assert_eq!(axis.num_bins(true), 26);
+        assert_eq!(axis.index(NEW_YEAR_2024), 0);
+        assert_eq!(axis.index(NEW_YEAR_2024 + 90 * 60 * NANOS_PER_SEC), 1);
+        assert_eq!(axis.index(NEW_YEAR_2024 - 1), axis.underflow());
+        assert_eq!(axis.index(NEW_YEAR_2024 + NANOS_PER_DAY), axis.overflow());
+        let time = UNIX_EPOCH + Duration::from_nanos(NEW_YEAR_2024 as u64) + hour * 5;
+        assert_eq!(axis.index_time(time).unwrap(), 5);
+        // beyond the i64 nanosecond range (about 292 years)
+        let far = UNIX_EPOCH + Duration::from_secs(400 * 365 * 86_400);
+        assert!(axis.index_time(far).is_err());
+        assert_eq!(axis.edge_labels()[1], "2024-01-01T01:00:00Z");
+    }
+
+    #[test]
+    fn test_datetime_calendar_axis() {
+        // starts mid-month, first edge is aligned to the first of the month
+        let start = NEW_YEAR_2024 + 40 * NANOS_PER_DAY;
+        let stop = NEW_YEAR_2024 + 100 * NANOS_PER_DAY;
+        let axis = DateTime::calendar(start, stop, Calendar::Month, 1).unwrap();
+        assert_eq!(
+            axis.edge_labels(),
+            vec![
+                "2024-02-01T00:00:00Z",
+                "2024-03-01T00:00:00Z",
+                "2024-04-01T00:00:00Z",
+                "2024-05-01T00:00:00Z",
+            ]
+        );
+        // leap year february
+        assert_eq!(axis.widths()[0], (29 * NANOS_PER_DAY) as f64);
+
+        let axis = DateTime::calendar(start, stop, Calendar::Day, 7).unwrap();
+        assert_eq!(axis.edge_labels()[0], "2024-02-10T00:00:00Z");
+        assert_eq!(axis.num_bins(false), 9);
+
+        assert!(DateTime::calendar(stop, start, Calendar::Day, 1).is_err());
+        assert!(DateTime::calendar(start, stop, Calendar::Year, 0).is_err());
+        // edges beyond the i64 nanosecond range
+        for unit in [Calendar::Day, Calendar::Month, Calendar::Year] {
+            assert!(DateTime::calendar(start, i64::MAX - 1, unit, u32::MAX).is_err());
+        }
+        assert_eq!(format_timestamp(-1), "1969-12-31T23:59:59.999999999Z");
+    }
+}
@@ -1,10 +1,13 @@
 use crate::axis::{Axis, AxisError};
-use crate::bin::SingleValue;
+use crate::bin::{Bin, SingleValue, Value};
+use crate::metadata::Metadata;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Integer {
     pub bins: Vec<SingleValue<i32>>,
+    pub metadata: Metadata,
 }
 
 impl Integer {
@@ -26,7 +29,10 @@ impl Integer {
             single_bins.push(SingleValue::new(bin));
         }
 
-        Ok(Self { bins: single_bins })
+        Ok(Self {
+            bins: single_bins,
+            metadata: Metadata::default(),
+        })
     }
 
     pub fn index(&self, value: i32) -> usize {
@@ -37,7 +43,17 @@ impl Integer {
     }
 }
 
+impl PartialEq for Integer {
+    fn eq(&self, other: &Self) -> bool {
+        self.check_equal(other).is_ok()
+    }
+}
+
 impl Axis for Integer {
+    fn kind(&self) -> &'static str {
+        "Integer"
+    }
+
     fn num_bins(&self, flow: bool) -> usize {
         let num = self.bins.len() - 1;
         if flow {
@@ -56,6 +72,23 @@ impl Axis for Integer {
     fn overflow(&self) -> usize {
         0
     }
+
+    fn bin(&self, index: usize) -> Option<Bin> {
+        if index == self.overflow() {
+            return None;
+        }
+        self.bins
+            .get(index)
+            .map(|bin| Bin::SingleValue(SingleValue::new(Value::Int(bin.value))))
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
 }
 
 #[cfg(test)]
@@ -76,4 +109,14 @@ mod tests {
         // overflow
         assert_eq!(axis.index(123), 0);
     }
+
+    #[test]
+    fn test_integer_axis_introspection() {
+        let axis = Integer::new(vec![3, 5, 9]).unwrap();
+        assert_eq!(axis.value(axis.index(5)), Some(Value::Int(5)));
+        assert_eq!(axis.value(axis.overflow()), None);
+        // categorical axes have unit-width bins
+        assert_eq!(axis.edges(), vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(axis.widths(), vec![1.0; 3]);
+    }
 }
@@ -1,22 +1,42 @@
 use anyhow::Result;
-use axis::AxisError;
+use axis::{Axis, AxisError};
 use category::Category;
+use datetime::DateTime;
 use integer::Integer;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 use uniform::Uniform;
 use variable::Variable;
 
 pub mod axis;
 pub mod bin;
 pub mod category;
+pub mod datetime;
 pub mod integer;
+pub mod metadata;
 pub mod uniform;
 pub mod variable;
 
+// Concrete axis types, e.g. to (de)serialize axes together with their metadata
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Axes {
     Uniform(Uniform),
     Variable(Variable),
     Category(Category),
     Integer(Integer),
+    DateTime(DateTime),
+}
+
+impl From<Axes> for Box<dyn Axis> {
+    fn from(axis: Axes) -> Self {
+        match axis {
+            Axes::Uniform(axis) => Box::new(axis),
+            Axes::Variable(axis) => Box::new(axis),
+            Axes::Category(axis) => Box::new(axis),
+            Axes::Integer(axis) => Box::new(axis),
+            Axes::DateTime(axis) => Box::new(axis),
+        }
+    }
 }
 
 pub trait AxisIndex<T> {
@@ -42,10 +62,34 @@ impl AxisIndex<i32> for Axes {
     }
 }
 
+impl AxisIndex<&str> for Axes {
+    fn index(&self, value: &str) -> Result<usize> {
+        match self {
+            Axes::Category(axis) => Ok(axis.index(value)),
+            _ => Err(AxisError::InvalidValueType.into()),
+        }
+    }
+}
+
 impl AxisIndex<String> for Axes {
     fn index(&self, value: String) -> Result<usize> {
+        self.index(value.as_str())
+    }
+}
+
+impl AxisIndex<i64> for Axes {
+    fn index(&self, value: i64) -> Result<usize> {
         match self {
-            Axes::Category(axis) => Ok(axis.index(value)),
+            Axes::DateTime(axis) => Ok(axis.index(value)),
+            _ => Err(AxisError::InvalidValueType.into()),
+        }
+    }
+}
+
+impl AxisIndex<SystemTime> for Axes {
+    fn index(&self, value: SystemTime) -> Result<usize> {
+        match self {
+            Axes::DateTime(axis) => axis.index_time(value),
             _ => Err(AxisError::InvalidValueType.into()),
         }
     }
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Optional axis metadata:
+// - name: identifies the axis in histogram lookups (e.g. `fill_by_name`, `slice_by_name`)
+// - label: human-readable description (e.g. LaTeX), falls back to the name
+// - attrs: arbitrary string attributes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub label: Option<String>,
+    pub attrs: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::axis::Axis;
+    use crate::uniform::Uniform;
+
+    #[test]
+    fn test_axis_metadata() {
+        let axis = Uniform::new(10, 0.0, 100.0)
+            .unwrap()
+            .with_name("pt")
+            .with_label("$p_T$ [GeV]")
+            .with_attr("unit", "GeV");
+        assert_eq!(axis.name(), Some("pt"));
+        assert_eq!(axis.label(), Some("$p_T$ [GeV]"));
+        assert_eq!(axis.metadata().attrs["unit"], "GeV");
+
+        // label falls back to the name
+        let axis = Uniform::new(10, 0.0, 100.0).unwrap().with_name("eta");
+        assert_eq!(axis.label(), Some("eta"));
+
+        // equality is structural like `check_equal`: the name counts, label and attributes do not
+        let labelled = axis.clone().with_label("$\\eta$").with_attr("unit", "rad");
+        assert!(labelled == axis && axis.check_equal(&labelled).is_ok());
+        assert!(axis.clone().with_name("phi") != axis);
+        let category = crate::category::Category::new(vec!["a".to_string()]).unwrap();
+        assert!(category.clone().with_label("A") == category);
+
+        // metadata survives cloning through the trait object
+        let boxed: Box<dyn Axis> = Box::new(axis);
+        assert_eq!(boxed.clone().name(), Some("eta"));
+    }
+
+    #[test]
+    fn test_axis_metadata_serialization() {
+        use crate::category::Category;
+        use crate::datetime::DateTime;
+        use crate::integer::Integer;
+        use crate::variable::Variable;
+        use crate::Axes;
+
+        let axes = vec![
+            Axes::Uniform(Uniform::new(10, 0.0, 100.0).unwrap().with_name("pt")),
+            Axes::Variable(Variable::new(vec![0.0, 1.0, 3.0]).unwrap().with_name("eta")),
+            Axes::Category(
+                Category::new(vec!["A".to_string(), "B".to_string()])
+                    .unwrap()
+                    .with_name("dataset")
+                    .with_label("Dataset")
+                    .with_attr("source", "nanoAOD"),
+            ),
+            Axes::Integer(Integer::new(vec![1, 2, 3]).unwrap().with_name("njets")),
+            Axes::DateTime(DateTime::new(vec![0, 60, 120]).unwrap().with_name("time")),
+        ];
+        let json = serde_json::to_string(&axes).unwrap();
+        let restored: Vec<Axes> = serde_json::from_str(&json).unwrap();
+        for (axis, restored) in axes.into_iter().zip(restored) {
+            let (axis, restored): (Box<dyn Axis>, Box<dyn Axis>) = (axis.into(), restored.into());
+            assert_eq!(restored.metadata(), axis.metadata());
+            assert!(restored.check_equal(axis.as_ref()).is_ok());
+        }
+
+        // the category lookup table is rebuilt
+        let json = r#"{"labels":["A","B"],"metadata":{"name":"c","label":null,"attrs":{}}}"#;
+        let category: Category = serde_json::from_str(json).unwrap();
+        assert_eq!(category.index("B"), 2);
+        let json = r#"{"labels":["A","A"],"metadata":{"name":null,"label":null,"attrs":{}}}"#;
+        assert!(serde_json::from_str::<Category>(json).is_err());
+    }
+}
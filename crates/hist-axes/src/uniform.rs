@@ -1,16 +1,19 @@
 use std::fmt::{Debug, Formatter};
 
 use crate::axis::{Axis, AxisError};
-use crate::bin::Interval;
+use crate::bin::{Bin, Interval};
+use crate::metadata::Metadata;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Uniform {
     pub bins: Vec<Interval<f32>>,
     pub low: f32,
     pub high: f32,
     pub step: f32,
     pub num: usize,
+    pub metadata: Metadata,
 }
 
 impl Uniform {
@@ -26,7 +29,7 @@ impl Uniform {
         for i in 0..num {
             let lo = start + i as f32 * step;
             let hi = lo + step;
-            bins.push(Interval::new(lo, hi));
+            bins.push(Interval::try_new(lo, hi)?);
         }
         Ok(Self {
             bins,
@@ -34,6 +37,7 @@ impl Uniform {
             high: stop,
             step,
             num,
+            metadata: Metadata::default(),
         })
     }
 
@@ -49,7 +53,17 @@ impl Uniform {
     }
 }
 
+impl PartialEq for Uniform {
+    fn eq(&self, other: &Self) -> bool {
+        self.check_equal(other).is_ok()
+    }
+}
+
 impl Axis for Uniform {
+    fn kind(&self) -> &'static str {
+        "Uniform"
+    }
+
     fn num_bins(&self, flow: bool) -> usize {
         if flow {
             // include underflow and overflow bins
@@ -57,17 +71,35 @@ impl Axis for Uniform {
         }
         self.num
     }
+
+    fn bin(&self, index: usize) -> Option<Bin> {
+        self.bins
+            .get(index)
+            .map(|bin| Bin::Interval(Interval::new(bin.low as f64, bin.high as f64)))
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
 }
 
 impl Debug for Uniform {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Uniform({:#?}..{:#?}, #{:#?} bins)",
+            "Uniform({:#?}..{:#?}, #{:#?} bins",
             self.low,
             self.high,
             self.num_bins(false),
-        )
+        )?;
+        if let Some(name) = self.name() {
+            write!(f, ", name={:?}", name)?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -83,5 +115,56 @@ mod tests {
         assert_eq!(axis.index(0.0), 0);
         assert_eq!(axis.index(0.1), 1);
         assert_eq!(axis.index(0.9), 9);
+        assert!(Uniform::new(10, 0.0, f32::NAN).is_err());
+        // bins too narrow for f32
+        assert!(Uniform::new(10, 1e8, 1e8 + 8.0).is_err());
+    }
+
+    #[test]
+    fn test_uniform_axis_introspection() {
+        let axis = Uniform::new(4, 0.0, 2.0).unwrap();
+        assert_eq!(axis.edges(), vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(axis.centers(), vec![0.25, 0.75, 1.25, 1.75]);
+        assert_eq!(axis.widths(), vec![0.5; 4]);
+        match axis.bin(1) {
+            Some(Bin::Interval(bin)) => assert_eq!((bin.low, bin.high), (0.5, 1.0)),
+            other => panic!("unexpected bin: {:?}", other),
+        }
+        // flow bins
+        assert!(axis.bin(axis.underflow()).is_none());
+        assert!(axis.bin(axis.overflow()).is_none());
+        assert!(axis.value(0).is_none());
+    }
+
+    #[test]
+    fn test_uniform_axis_equality() {
+        use crate::axis::AxisMismatch;
+        use crate::variable::Variable;
+
+        let axis = Uniform::new(4, 0.0, 2.0).unwrap();
+        assert_eq!(axis, Uniform::new(4, 0.0, 2.0).unwrap());
+        assert!(axis
+            .check_equal(&Uniform::new(4, 0.0, 2.0).unwrap())
+            .is_ok());
+        assert_eq!(
+            axis.check_equal(&Uniform::new(5, 0.0, 2.0).unwrap()),
+            Err(AxisMismatch::NumBins(4, 5))
+        );
+        assert_eq!(
+            axis.check_equal(&Uniform::new(4, 0.0, 4.0).unwrap()),
+            Err(AxisMismatch::Edge {
+                index: 1,
+                left: 0.5,
+                right: 1.0
+            })
+        );
+        let variable = Variable::new(vec![0.0, 0.5, 1.0, 1.5, 2.0]).unwrap();
+        assert_eq!(
+            axis.check_compatible(&variable),
+            Err(AxisMismatch::Kind("Uniform", "Variable"))
+        );
+        assert!(axis
+            .check_equal(&Uniform::new(4, 0.0, 2.0).unwrap().with_name("x"))
+            .is_err());
     }
 }
@@ -1,10 +1,13 @@
 use crate::axis::{Axis, AxisError};
-use crate::bin::Interval;
+use crate::bin::{Bin, Interval};
+use crate::metadata::Metadata;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variable {
     pub bins: Vec<Interval<f32>>,
+    pub metadata: Metadata,
 }
 
 impl Variable {
@@ -19,7 +22,125 @@ impl Variable {
             .windows(2)
             .map(|w| Interval::new(w[0], w[1]))
             .collect();
-        Ok(Self { bins })
+        Ok(Self {
+            bins,
+            metadata: Metadata::default(),
+        })
+    }
+
+    // equal-population bins: edges at the `num`-quantiles of `sample`
+    // (linear interpolation between order statistics, duplicate edges are dropped)
+    pub fn from_quantiles(sample: &[f32], num: usize) -> Result<Self> {
+        if num == 0 {
+            return Err(AxisError::InvalidNumberOfBins.into());
+        }
+        if sample.is_empty() || sample.iter().any(|v| !v.is_finite()) {
+            return Err(AxisError::InvalidSample.into());
+        }
+        let mut sorted = sample.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let last = (sorted.len() - 1) as f64;
+        let mut edges: Vec<f32> = (0..=num)
+            .map(|i| {
+                let pos = last * i as f64 / num as f64;
+                let (lo, frac) = (pos.floor() as usize, pos.fract());
+                let hi = (lo + 1).min(sorted.len() - 1);
+                (sorted[lo] as f64 + frac * (sorted[hi] as f64 - sorted[lo] as f64)) as f32
+            })
+            .collect();
+        edges.dedup();
+        Self::new(edges)
+    }
+
+    // `num` bins with edges evenly spaced on a log scale between `start` and `stop`
+    pub fn geomspace(num: usize, start: f32, stop: f32) -> Result<Self> {
+        if num == 0 {
+            return Err(AxisError::InvalidNumberOfBins.into());
+        }
+        if !(start > 0.0 && stop > start) {
+            return Err(AxisError::InvalidLogRange.into());
+        }
+        let ratio = (stop as f64 / start as f64).ln();
+        let mut edges: Vec<f32> = (0..=num)
+            .map(|i| (start as f64 * (ratio * i as f64 / num as f64).exp()) as f32)
+            .collect();
+        // avoid rounding errors on the range boundaries
+        edges[0] = start;
+        edges[num] = stop;
+        Self::new(edges)
+    }
+
+    // `num` bins with edges `base^x` for `x` evenly spaced between `start` and `stop`
+    pub fn logspace(num: usize, start: f32, stop: f32, base: f32) -> Result<Self> {
+        if !(base > 0.0 && base != 1.0) {
+            return Err(AxisError::InvalidLogRange.into());
+        }
+        Self::geomspace(num, base.powf(start), base.powf(stop))
+    }
+
+    // merges neighbouring bins of a continuous `axis`,
+    // `groups` holds the number of original bins in each new bin
+    pub fn merged(axis: &dyn Axis, groups: &[usize]) -> Result<Self> {
+        let edges = continuous_edges(axis)?;
+        let nbins = edges.len() - 1;
+        if groups.contains(&0) || groups.iter().sum::<usize>() != nbins {
+            return Err(AxisError::InvalidBinGroups { nbins }.into());
+        }
+        let mut merged = Vec::with_capacity(groups.len() + 1);
+        merged.push(edges[0]);
+        let mut pos = 0;
+        for size in groups {
+            pos += size;
+            merged.push(edges[pos]);
+        }
+        Self::new(merged)
+    }
+
+    // merges every `factor` neighbouring bins of a continuous `axis`
+    pub fn rebin(axis: &dyn Axis, factor: usize) -> Result<Self> {
+        let nbins = axis.num_bins(false);
+        if factor == 0 || !nbins.is_multiple_of(factor) {
+            return Err(AxisError::InvalidBinGroups { nbins }.into());
+        }
+        Self::merged(axis, &vec![factor; nbins / factor])
+    }
+
+    // merges neighbouring bins of a fine continuous `axis` (from low to high) until
+    // the relative statistical uncertainty sqrt(sum(variances)) / sum(values) of each
+    // new bin is at most `target`, a remaining tail is merged into the last bin
+    pub fn from_relative_uncertainty(
+        axis: &dyn Axis,
+        values: &[f64],
+        variances: &[f64],
+        target: f64,
+    ) -> Result<Self> {
+        let nbins = axis.num_bins(false);
+        for ncontents in [values.len(), variances.len()] {
+            if ncontents != nbins {
+                return Err(AxisError::BinContentMismatch { nbins, ncontents }.into());
+            }
+        }
+        if target.is_nan() || target <= 0.0 {
+            return Err(AxisError::InvalidUncertaintyTarget.into());
+        }
+        let mut groups = Vec::new();
+        let (mut size, mut sum, mut var) = (0, 0.0, 0.0);
+        for (value, variance) in values.iter().zip(variances.iter()) {
+            size += 1;
+            sum += value;
+            var += variance;
+            if sum > 0.0 && var.sqrt() / sum <= target {
+                groups.push(size);
+                (size, sum, var) = (0, 0.0, 0.0);
+            }
+        }
+        if size > 0 {
+            match groups.last_mut() {
+                Some(last) => *last += size,
+                None => groups.push(size),
+            }
+        }
+        Self::merged(axis, &groups)
     }
 
     pub fn index(&self, value: f32) -> usize {
@@ -45,7 +166,17 @@ impl Variable {
     }
 }
 
+impl PartialEq for Variable {
+    fn eq(&self, other: &Self) -> bool {
+        self.check_equal(other).is_ok()
+    }
+}
+
 impl Axis for Variable {
+    fn kind(&self) -> &'static str {
+        "Variable"
+    }
+
     fn num_bins(&self, flow: bool) -> usize {
         if flow {
             // include underflow and overflow bins
@@ -53,6 +184,31 @@ impl Axis for Variable {
         }
         self.bins.len()
     }
+
+    fn bin(&self, index: usize) -> Option<Bin> {
+        self.bins
+            .get(index)
+            .map(|bin| Bin::Interval(Interval::new(bin.low as f64, bin.high as f64)))
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+}
+
+fn continuous_edges(axis: &dyn Axis) -> Result<Vec<f32>> {
+    if axis
+        .bins()
+        .iter()
+        .any(|bin| !matches!(bin, Bin::Interval(_)))
+    {
+        return Err(AxisError::ExpectedContinuousAxis.into());
+    }
+    Ok(axis.edges().into_iter().map(|edge| edge as f32).collect())
 }
 
 #[cfg(test)]
@@ -74,4 +230,52 @@ mod tests {
         assert_eq!(var.index(2.0), 2);
         assert_eq!(var.index(2.5), 2);
     }
+
+    #[test]
+    fn test_variable_axis_introspection() {
+        let var = Variable::new(vec![0.0, 1.0, 3.0, 7.0]).unwrap();
+        assert_eq!(var.edges(), vec![0.0, 1.0, 3.0, 7.0]);
+        assert_eq!(var.centers(), vec![0.5, 2.0, 5.0]);
+        assert_eq!(var.widths(), vec![1.0, 2.0, 4.0]);
+        assert_eq!(var.bins().len(), 3);
+        assert!(var.bin(var.overflow()).is_none());
+    }
+
+    #[test]
+    fn test_variable_axis_helpers() {
+        use crate::integer::Integer;
+        use crate::uniform::Uniform;
+
+        let sample: Vec<f32> = (0..=100).map(|i| i as f32).collect();
+        let var = Variable::from_quantiles(&sample, 4).unwrap();
+        assert_eq!(var.edges(), vec![0.0, 25.0, 50.0, 75.0, 100.0]);
+        assert!(Variable::from_quantiles(&[], 4).is_err());
+        assert!(Variable::from_quantiles(&[1.0, f32::NAN], 4).is_err());
+
+        let var = Variable::geomspace(3, 1.0, 1000.0).unwrap();
+        let edges = var.edges();
+        assert_eq!(edges.len(), 4);
+        assert!((edges[1] - 10.0).abs() < 1e-4);
+        assert!((edges[2] - 100.0).abs() < 1e-3);
+        assert!(Variable::geomspace(3, 0.0, 1.0).is_err());
+        let var = Variable::logspace(2, 0.0, 2.0, 10.0).unwrap();
+        assert_eq!(var.edges(), vec![1.0, 10.0, 100.0]);
+
+        let uniform = Uniform::new(6, 0.0, 6.0).unwrap();
+        let var = Variable::rebin(&uniform, 2).unwrap();
+        assert_eq!(var.edges(), vec![0.0, 2.0, 4.0, 6.0]);
+        let var = Variable::merged(&uniform, &[1, 4, 1]).unwrap();
+        assert_eq!(var.edges(), vec![0.0, 1.0, 5.0, 6.0]);
+        assert!(Variable::rebin(&uniform, 4).is_err());
+        assert!(Variable::merged(&uniform, &[1, 2]).is_err());
+        let integer = Integer::new(vec![1, 2]).unwrap();
+        assert!(Variable::rebin(&integer, 1).is_err());
+
+        // relative uncertainty of 50% needs at least four counts per bin
+        let counts = vec![1.0, 1.0, 4.0, 0.0, 2.0, 2.0, 1.0];
+        let fine = Uniform::new(7, 0.0, 7.0).unwrap();
+        let var = Variable::from_relative_uncertainty(&fine, &counts, &counts, 0.5).unwrap();
+        assert_eq!(var.edges(), vec![0.0, 3.0, 7.0]);
+        assert!(Variable::from_relative_uncertainty(&fine, &counts, &counts, 0.0).is_err());
+    }
 }
@@ -1,2 +1,3 @@
 pub mod arrayhist;
+pub mod smooth;
 pub mod vechist;
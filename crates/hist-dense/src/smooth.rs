@@ -0,0 +1,510 @@
+// Smoothing of dense histograms:
+// - `smooth_353qh`: running medians 3, 5, 3, quadratic interpolation of flat segments and
+//   Hanning smoothing, applied twice (on the residuals) like ROOT's `TH1::Smooth`
+// - `gaussian`: separable Gaussian kernel convolution, the content of a bin is spread with the
+//   integral of the kernel over each target bin (wide bins receive more)
+// - `adaptive_kde`: fills a histogram from unbinned points with an adaptive Gaussian kernel
+//   density estimate (Abramson's square-root law on a fixed-bandwidth pilot estimate)
+use crate::vechist::VecHist;
+use anyhow::Result;
+use hist::hist::Histogram;
+use hist::normal_cdf;
+use hist_axes::axis::Axis;
+use hist_axes::bin::Bin;
+use hist_storages::StorageElement;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SmoothError {
+    #[error("expected a 1-D histogram, got {0} axes")]
+    NotOneDimensional(usize),
+    #[error("353QH smoothing needs at least 3 bins, got {0}")]
+    TooFewBins(usize),
+    #[error("axis {0} is not continuous")]
+    NotContinuous(usize),
+    #[error("got {got} values for {naxes} axes")]
+    AxesMismatch { got: usize, naxes: usize },
+    #[error("kernel width {0} must be finite and not negative")]
+    InvalidWidth(f64),
+    #[error("kernel density estimation needs at least 2 points")]
+    TooFewPoints,
+    #[error("points have no spread along axis {0}")]
+    ZeroSpread(usize),
+    #[error("smoothing needs a floating-point storage, integer bins would be truncated")]
+    IntegerStorage,
+    #[error("smoothing needs a storage of weights, profile bins hold the mean of their samples")]
+    ProfileStorage,
+}
+
+// smooths the regular bins of a 1-D histogram `ntimes`,
+// bins are rescaled to the smoothed value, empty bins are set to it with the variance per unit
+// value of the non-empty bins (Poisson for unit weights)
+pub fn smooth_353qh<S: StorageElement>(hist: &VecHist<S>, ntimes: usize) -> Result<VecHist<S>> {
+    check_storage(&hist.zero)?;
+    if hist.axes.len() != 1 {
+        return Err(SmoothError::NotOneDimensional(hist.axes.len()).into());
+    }
+    let order = hist.axes[0].display_order(false);
+    if order.len() < 3 {
+        return Err(SmoothError::TooFewBins(order.len()).into());
+    }
+    let original: Vec<f64> = order.iter().map(|&idx| hist.data[idx].value()).collect();
+    let mut values = original.clone();
+    for _ in 0..ntimes {
+        smooth_array(&mut values);
+    }
+
+    let (sum_of_values, sum_of_variances) = order
+        .iter()
+        .map(|&idx| &hist.data[idx])
+        .filter(|bin| bin.value() != 0.0)
+        .fold((0.0, 0.0), |(values, variances), bin| {
+            (values + bin.value().abs(), variances + bin.variance())
+        });
+    let variance_per_value = if sum_of_values > 0.0 {
+        sum_of_variances / sum_of_values
+    } else {
+        1.0
+    };
+
+    let mut smoothed = copy(hist);
+    for ((&idx, &old), &new) in order.iter().zip(original.iter()).zip(values.iter()) {
+        let bin = &mut smoothed.data[idx];
+        if old != 0.0 {
+            bin.scale(new / old);
+        } else if new != 0.0 {
+            bin.set(new, new.abs() * variance_per_value)?;
+        }
+    }
+    Ok(smoothed)
+}
+
+// convolution with a Gaussian kernel of standard deviation `widths[axis]` (in axis units),
+// a width of zero leaves the axis untouched (required for categorical axes),
+// flow bins are not smoothed and the kernel is normalised to the regular bins
+pub fn gaussian<S: StorageElement>(hist: &VecHist<S>, widths: &[f64]) -> Result<VecHist<S>> {
+    check_storage(&hist.zero)?;
+    if widths.len() != hist.axes.len() {
+        return Err(SmoothError::AxesMismatch {
+            got: widths.len(),
+            naxes: hist.axes.len(),
+        }
+        .into());
+    }
+    for (axis, &width) in widths.iter().enumerate() {
+        if !(width.is_finite() && width >= 0.0) {
+            return Err(SmoothError::InvalidWidth(width).into());
+        }
+        if width > 0.0 && !is_continuous(hist.axes[axis].as_ref()) {
+            return Err(SmoothError::NotContinuous(axis).into());
+        }
+    }
+
+    let mut smoothed = copy(hist);
+    for (axis, &width) in widths.iter().enumerate() {
+        if width > 0.0 {
+            smoothed = convolve(&smoothed, axis, width)?;
+        }
+    }
+    Ok(smoothed)
+}
+
+// fills `hist` with a kernel density estimate of `points` (one coordinate per axis) of unit
+// weight each, the tails of the kernels beyond the axis ranges end up in the flow bins;
+// the pilot bandwidths follow Silverman's rule times `scale`
+pub fn adaptive_kde<S: StorageElement>(
+    hist: &mut VecHist<S>,
+    points: &[Vec<f64>],
+    scale: f64,
+) -> Result<()> {
+    check_storage(&hist.zero)?;
+    let naxes = hist.axes.len();
+    for (axis, axis_ref) in hist.axes.iter().enumerate() {
+        if !is_continuous(axis_ref.as_ref()) {
+            return Err(SmoothError::NotContinuous(axis).into());
+        }
+    }
+    if points.len() < 2 {
+        return Err(SmoothError::TooFewPoints.into());
+    }
+    if let Some(point) = points.iter().find(|point| point.len() != naxes) {
+        return Err(SmoothError::AxesMismatch {
+            got: point.len(),
+            naxes,
+        }
+        .into());
+    }
+    if !(scale.is_finite() && scale > 0.0) {
+        return Err(SmoothError::InvalidWidth(scale).into());
+    }
+
+    let n = points.len() as f64;
+    let rule = (4.0 / ((naxes as f64 + 2.0) * n)).powf(1.0 / (naxes as f64 + 4.0));
+    let pilot_widths = (0..naxes)
+        .map(|axis| {
+            let column: Vec<f64> = points.iter().map(|point| point[axis]).collect();
+            let spread = spread(&column);
+            if spread > 0.0 {
+                Ok(scale * rule * spread)
+            } else {
+                Err(SmoothError::ZeroSpread(axis))
+            }
+        })
+        .collect::<Result<Vec<f64>, SmoothError>>()?;
+
+    // fixed-bandwidth pilot density at each point
+    let pilot: Vec<f64> = points
+        .iter()
+        .map(|x| {
+            points
+                .iter()
+                .map(|y| {
+                    (0..naxes)
+                        .map(|a| normal_pdf((x[a] - y[a]) / pilot_widths[a]) / pilot_widths[a])
+                        .product::<f64>()
+                })
+                .sum::<f64>()
+                / n
+        })
+        .collect();
+    let geometric_mean = (pilot.iter().map(|f| f.ln()).sum::<f64>() / n).exp();
+
+    // `hist` is left untouched if a fill fails
+    let mut filled = copy(hist);
+    for (point, density) in points.iter().zip(pilot.iter()) {
+        // narrower kernels where the pilot density is high
+        let local = (geometric_mean / density).sqrt();
+        let mut terms = vec![(0, 1.0)];
+        for (a, axis) in hist.axes.iter().enumerate() {
+            let width = pilot_widths[a] * local;
+            let cdf: Vec<f64> = axis
+                .edges()
+                .iter()
+                .map(|edge| normal_cdf((edge - point[a]) / width))
+                .collect();
+            let mut probabilities: Vec<(usize, f64)> = cdf
+                .windows(2)
+                .enumerate()
+                .map(|(i, w)| (i, w[1] - w[0]))
+                .collect();
+            probabilities.push((axis.underflow(), cdf[0]));
+            probabilities.push((axis.overflow(), 1.0 - cdf[cdf.len() - 1]));
+            let nbins = axis.num_bins(true);
+            terms = terms
+                .iter()
+                .flat_map(|&(idx, weight)| {
+                    probabilities
+                        .iter()
+                        .filter(|(_, p)| *p > 0.0)
+                        .map(move |&(i, p)| (idx * nbins + i, weight * p))
+                })
+                .collect();
+        }
+        for (idx, weight) in terms {
+            filled.data[idx].fill(weight as f32)?;
+        }
+    }
+    *hist = filled;
+    Ok(())
+}
+
+// one pass of 353QH twice on `xx` (at least 3 values)
+fn smooth_array(xx: &mut [f64]) {
+    let nn = xx.len();
+    let mut zz = xx.to_vec();
+    let mut yy = vec![0.0; nn];
+    let mut rr = vec![0.0; nn];
+
+    // second iteration smooths the residuals of the first one
+    for iteration in 0..2 {
+        // running medians of 3, 5 and 3
+        for kk in 0..3 {
+            yy.copy_from_slice(&zz);
+            let (size, first) = if kk == 1 { (5, 2) } else { (3, 1) };
+            for ii in first..nn.saturating_sub(first) {
+                zz[ii] = median(&yy[ii - first..ii - first + size]);
+            }
+            if kk == 0 {
+                // end points: median of the neighbour, itself and the extrapolation
+                zz[0] = median(&[zz[1], zz[0], 3.0 * zz[1] - 2.0 * zz[2]]);
+                zz[nn - 1] = median(&[zz[nn - 2], zz[nn - 1], 3.0 * zz[nn - 2] - 2.0 * zz[nn - 3]]);
+            }
+            if kk == 1 {
+                zz[1] = median(&yy[..3]);
+                zz[nn - 2] = median(&yy[nn - 3..]);
+            }
+        }
+
+        // quadratic interpolation of flat segments of three points
+        yy.copy_from_slice(&zz);
+        for ii in 2..nn.saturating_sub(2) {
+            if zz[ii - 1] != zz[ii] || zz[ii] != zz[ii + 1] {
+                continue;
+            }
+            let (left, right) = (zz[ii - 2] - zz[ii], zz[ii + 2] - zz[ii]);
+            if left * right <= 0.0 {
+                continue;
+            }
+            // interpolate towards the side with the smaller step
+            let (back, near, ahead) = if right.abs() > left.abs() {
+                (ii + 2, ii - 1, ii - 2)
+            } else {
+                (ii - 2, ii + 1, ii + 2)
+            };
+            yy[ii] = -0.5 * zz[back] + zz[ii] / 0.75 + zz[ahead] / 6.0;
+            yy[near] = 0.5 * (zz[ahead] - zz[back]) + zz[ii];
+        }
+
+        // Hanning: running means with weights 1/4, 1/2, 1/4
+        for ii in 1..nn - 1 {
+            zz[ii] = 0.25 * yy[ii - 1] + 0.5 * yy[ii] + 0.25 * yy[ii + 1];
+        }
+        zz[0] = yy[0];
+        zz[nn - 1] = yy[nn - 1];
+
+        if iteration == 0 {
+            rr.copy_from_slice(&zz);
+            for (z, x) in zz.iter_mut().zip(xx.iter()) {
+                *z = x - *z;
+            }
+        }
+    }
+
+    // smoothed values plus smoothed residuals, kept positive for positive inputs
+    let positive = xx.iter().all(|&x| x >= 0.0);
+    for ((x, r), z) in xx.iter_mut().zip(rr.iter()).zip(zz.iter()) {
+        *x = if positive { (r + z).max(0.0) } else { r + z };
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted[sorted.len() / 2]
+}
+
+// spreads all bins of `hist` along the continuous `axis`
+fn convolve<S: StorageElement>(hist: &VecHist<S>, axis: usize, width: f64) -> Result<VecHist<S>> {
+    let edges = hist.axes[axis].edges();
+    let nbins = edges.len() - 1;
+    // kernel[j][i]: fraction of the content of bin j that moves to bin i
+    let kernel: Vec<Vec<f64>> = (0..nbins)
+        .map(|j| {
+            let center = 0.5 * (edges[j] + edges[j + 1]);
+            let cdf: Vec<f64> = edges
+                .iter()
+                .map(|edge| normal_cdf((edge - center) / width))
+                .collect();
+            let total = cdf[nbins] - cdf[0];
+            cdf.windows(2).map(|w| (w[1] - w[0]) / total).collect()
+        })
+        .collect();
+
+    let mut convolved = hist.empty_like(hist.axes.clone());
+    for (idx, bin) in hist.data.iter().enumerate() {
+        let mut indices = hist.unravel_index(idx);
+        let source = indices[axis];
+        if source >= nbins {
+            convolved.data[idx].merge(bin)?;
+            continue;
+        }
+        for (target, &fraction) in kernel[source].iter().enumerate() {
+            if fraction == 0.0 {
+                continue;
+            }
+            indices[axis] = target;
+            let mut part = bin.clone();
+            part.scale(fraction);
+            let target_idx = convolved.stride_index(&indices)?;
+            convolved.data[target_idx].merge(&part)?;
+        }
+    }
+    Ok(convolved)
+}
+
+fn check_storage<S: StorageElement>(zero: &S) -> Result<(), SmoothError> {
+    if zero.is_integer() {
+        return Err(SmoothError::IntegerStorage);
+    }
+    if zero.is_profile() {
+        return Err(SmoothError::ProfileStorage);
+    }
+    Ok(())
+}
+
+fn copy<S: StorageElement>(hist: &VecHist<S>) -> VecHist<S> {
+    VecHist {
+        axes: hist.axes.clone(),
+        data: hist.data.clone(),
+        zero: hist.zero.clone(),
+    }
+}
+
+fn is_continuous(axis: &dyn Axis) -> bool {
+    axis.bins()
+        .iter()
+        .all(|bin| matches!(bin, Bin::Interval(_)))
+}
+
+fn normal_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+// robust standard deviation: min(std, IQR / 1.349), the standard deviation if the IQR vanishes
+fn spread(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let quantile = |q: f64| {
+        let pos = q * (n - 1.0);
+        let (lo, frac) = (pos.floor() as usize, pos.fract());
+        let hi = (lo + 1).min(sorted.len() - 1);
+        sorted[lo] + frac * (sorted[hi] - sorted[lo])
+    };
+    let iqr = quantile(0.75) - quantile(0.25);
+    if iqr > 0.0 {
+        std.min(iqr / 1.349)
+    } else {
+        std
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hist_axes::category::Category;
+    use hist_axes::uniform::Uniform;
+    use hist_axes::variable::Variable;
+    use hist_storages::{Storage, StorageType};
+
+    // inverse of `normal_cdf` by bisection
+    fn normal_quantile(p: f64) -> f64 {
+        let (mut low, mut high) = (-10.0, 10.0);
+        for _ in 0..100 {
+            let mid = 0.5 * (low + high);
+            if normal_cdf(mid) < p {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        0.5 * (low + high)
+    }
+
+    fn filled(axis: Box<dyn Axis>, values: &[f32], storage: StorageType) -> VecHist {
+        let mut hist = VecHist::new(vec![axis], storage);
+        for (idx, &value) in values.iter().enumerate() {
+            hist.fill(&[idx], value).unwrap();
+        }
+        hist
+    }
+
+    #[test]
+    fn test_smooth_353qh() {
+        let axis = || Box::new(Uniform::new(8, 0.0, 8.0).unwrap()) as Box<dyn Axis>;
+        // straight lines are kept
+        let ramp: Vec<f32> = (1..=8).map(|i| i as f32).collect();
+        let smoothed = smooth_353qh(&filled(axis(), &ramp, StorageType::Double), 1).unwrap();
+        assert_eq!(
+            smoothed.values(false),
+            ramp.iter().map(|&v| v as f64).collect::<Vec<_>>()
+        );
+
+        // a single spike is removed, the relative uncertainties are kept
+        let spike = [2.0, 2.0, 2.0, 20.0, 2.0, 2.0, 2.0, 2.0];
+        let hist = filled(axis(), &spike, StorageType::Weight);
+        let smoothed = smooth_353qh(&hist, 2).unwrap();
+        assert!(smoothed
+            .values(false)
+            .iter()
+            .all(|&v| (v - 2.0).abs() < 1e-6));
+        assert_eq!(smoothed.data[3], Storage::Weight((2.0, 4.0)));
+
+        assert!(smooth_353qh(
+            &filled(
+                Box::new(Uniform::new(2, 0.0, 2.0).unwrap()),
+                &[1.0, 2.0],
+                StorageType::Double
+            ),
+            1
+        )
+        .is_err());
+        // an empty bin gets the variance per unit value of the others (Poisson for unit weights)
+        let mut hist = VecHist::new(vec![axis()], StorageType::Weight);
+        for idx in (0..8).filter(|&idx| idx != 3) {
+            for _ in 0..4 {
+                hist.fill(&[idx], 1.0).unwrap();
+            }
+        }
+        let smoothed = smooth_353qh(&hist, 1).unwrap();
+        let (value, variance) = (smoothed.data[3].value(), smoothed.data[3].variance());
+        assert!((value - 4.0).abs() < 1e-6);
+        assert!((variance - value).abs() < 1e-6);
+
+        // integer bins cannot hold the rescaled values, profile bins hold means
+        let int = filled(axis(), &spike, StorageType::Int);
+        assert!(smooth_353qh(&int, 1).is_err());
+        assert!(gaussian(&int, &[1.0]).is_err());
+        let mean = VecHist::new(vec![axis()], StorageType::Mean);
+        assert!(smooth_353qh(&mean, 1).is_err());
+        assert!(gaussian(&mean, &[1.0]).is_err());
+    }
+
+    #[test]
+    fn test_gaussian() {
+        let var = Variable::new(vec![0.0, 1.0, 3.0, 4.0]).unwrap();
+        let hist = filled(Box::new(var.clone()), &[6.0], StorageType::Weight);
+        let smoothed = gaussian(&hist, &[1.0]).unwrap();
+        // the wide bin receives the integral of the kernel over [1, 3]
+        let cdf = |x: f64| normal_cdf(x - 0.5);
+        let expected = 6.0 * (cdf(3.0) - cdf(1.0)) / (cdf(4.0) - cdf(0.0));
+        assert!((smoothed.values(false)[1] - expected).abs() < 1e-5);
+        assert!((smoothed.sum(false) - 6.0).abs() < 1e-5);
+        // a width of zero keeps the histogram
+        assert_eq!(gaussian(&hist, &[0.0]).unwrap().data, hist.data);
+
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let axes = vec![
+            Box::new(Uniform::new(5, 0.0, 5.0).unwrap()) as Box<dyn Axis>,
+            Box::new(cat.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = VecHist::new(axes, StorageType::Double);
+        hist.fill(&[2, cat.index("b")], 10.0).unwrap();
+        let smoothed = gaussian(&hist, &[0.8, 0.0]).unwrap();
+        let values = smoothed.values(false);
+        // row-major (x, category): symmetric around x = 2, nothing leaks into "a"
+        assert!((values[3] - values[7]).abs() < 1e-5);
+        assert!(values[5] < 10.0);
+        assert!(values.iter().step_by(2).all(|&v| v == 0.0));
+        assert!(gaussian(&hist, &[0.8, 1.0]).is_err());
+        assert!(gaussian(&hist, &[0.8]).is_err());
+        assert!(gaussian(&hist, &[-1.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_kde() {
+        let points: Vec<Vec<f64>> = (0..400)
+            .map(|i| vec![normal_quantile((i as f64 + 0.5) / 400.0)])
+            .collect();
+        let axis = Uniform::new(40, -3.0, 3.0).unwrap();
+        let mut hist = VecHist::new(vec![Box::new(axis.clone())], StorageType::Double);
+        adaptive_kde(&mut hist, &points, 1.0).unwrap();
+        // every point has unit weight, the tails end up in the flow bins
+        assert!((hist.sum(true) - 400.0).abs() < 1e-2);
+        assert!(hist.sum(false) < 400.0);
+        assert!(hist.mean(0).unwrap().abs() < 1e-3);
+        let std = hist.std(0).unwrap();
+        assert!(std > 0.95 && std < 1.15);
+
+        assert!(adaptive_kde(&mut hist, &points[..1], 1.0).is_err());
+        assert!(adaptive_kde(&mut hist, &[vec![1.0], vec![1.0]], 1.0).is_err());
+        assert!(adaptive_kde(&mut hist, &[vec![1.0, 2.0], vec![0.0, 1.0]], 1.0).is_err());
+        assert!(adaptive_kde(&mut hist, &points, 0.0).is_err());
+        let mut int = VecHist::new(vec![Box::new(axis.clone())], StorageType::Int);
+        assert!(adaptive_kde(&mut int, &points, 1.0).is_err());
+        let mut mean = VecHist::new(vec![Box::new(axis)], StorageType::WeightedMean);
+        assert!(adaptive_kde(&mut mean, &points, 1.0).is_err());
+    }
+}
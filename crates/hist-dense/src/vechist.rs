@@ -1,46 +1,151 @@
 use anyhow::Result;
-use hist::hist::{HistError, Histogram};
+use hist::hist::{variation_of, HistError, Histogram};
 use hist_axes::axis::Axis;
-use hist_storages::{Storage, StorageType};
+use hist_storages::multiweight::MultiWeight;
+use hist_storages::unlimited::Unlimited;
+use hist_storages::{Storage, StorageElement, StorageError, StorageType};
 use std::fmt::{Debug, Formatter};
 
-// Holds the data as a flat vector
-pub struct VecHist {
+// Holds the data as a flat vector (or another flat buffer `D`, e.g. `Unlimited`)
+pub struct VecHist<S = Storage, D = Vec<S>> {
     pub axes: Vec<Box<dyn Axis>>,
-    pub data: Vec<Storage>,
-    pub storage: StorageType,
+    pub data: D,
+    pub zero: S,
+}
+
+// Dense histogram on a single packed `Unlimited` buffer,
+// the counter width grows on overflow or the first non-integer weight
+pub type UnlimitedHist = VecHist<f64, Unlimited>;
+
+// Flat buffer holding the bins of a `VecHist`
+pub trait Buffer<S> {
+    // buffer of `size` empty bins
+    fn empty(size: usize, zero: &S) -> Self;
+
+    fn get(&self, idx: usize) -> S;
+
+    fn update(&mut self, idx: usize, f: &mut dyn FnMut(&mut S));
+
+    fn fill(&mut self, idx: usize, weight: f32) -> Result<(), StorageError>;
+}
+
+impl<S: StorageElement> Buffer<S> for Vec<S> {
+    fn empty(size: usize, zero: &S) -> Self {
+        vec![zero.clone(); size]
+    }
+
+    fn get(&self, idx: usize) -> S {
+        self[idx].clone()
+    }
+
+    fn update(&mut self, idx: usize, f: &mut dyn FnMut(&mut S)) {
+        f(&mut self[idx])
+    }
+
+    fn fill(&mut self, idx: usize, weight: f32) -> Result<(), StorageError> {
+        self[idx].fill(weight)
+    }
+}
+
+// bins are exposed as `f64`, the buffer promotes itself when they are written back
+impl Buffer<f64> for Unlimited {
+    fn empty(size: usize, _zero: &f64) -> Self {
+        Unlimited::new(size)
+    }
+
+    fn get(&self, idx: usize) -> f64 {
+        Unlimited::get(self, idx)
+    }
+
+    fn update(&mut self, idx: usize, f: &mut dyn FnMut(&mut f64)) {
+        let mut bin = Unlimited::get(self, idx);
+        f(&mut bin);
+        self.set(idx, bin);
+    }
+
+    fn fill(&mut self, idx: usize, weight: f32) -> Result<(), StorageError> {
+        Unlimited::fill(self, idx, weight);
+        Ok(())
+    }
 }
 
 impl VecHist {
     pub fn new(axes: Vec<Box<dyn Axis>>, storage: StorageType) -> Self {
+        Self::with_storage(axes, storage.zero())
+    }
+
+    // copy of the histogram with bins converted to another storage type
+    pub fn convert(&self, storage: StorageType) -> Result<Self> {
+        Ok(Self {
+            axes: self.axes.clone(),
+            data: self
+                .data
+                .iter()
+                .map(|bin| bin.convert(&storage))
+                .collect::<Result<Vec<Storage>, StorageError>>()?,
+            zero: storage.zero(),
+        })
+    }
+}
+
+impl VecHist<MultiWeight> {
+    // variation `k` as a standalone histogram with `Weight` storage
+    // (`Double` if the multi-weight storage keeps no sums of squared weights)
+    pub fn variation(&self, k: usize) -> Result<VecHist> {
+        let empty = VecHist::with_storage(self.axes.clone(), self.zero.variation(k)?);
+        variation_of(self, k, empty)
+    }
+}
+
+impl<S: StorageElement> VecHist<S> {
+    // histogram with any storage, `zero` is the content of an empty bin
+    pub fn with_storage(axes: Vec<Box<dyn Axis>>, zero: S) -> Self {
         let dims = axes.iter().map(|axis| axis.num_bins(true)).product();
 
-        let init_val = match storage {
-            StorageType::Double => Storage::Double(0.0),
-            StorageType::Int => Storage::Int(0),
-            StorageType::Weight => Storage::Weight((0.0, 0.0)),
-        };
+        let data = vec![zero.clone(); dims];
+
+        Self { axes, data, zero }
+    }
+}
 
-        let data = vec![init_val; dims];
+impl UnlimitedHist {
+    pub fn unlimited(axes: Vec<Box<dyn Axis>>) -> Self {
+        let dims = axes.iter().map(|axis| axis.num_bins(true)).product();
 
         Self {
             axes,
-            data,
-            storage,
+            data: Unlimited::new(dims),
+            zero: 0.0,
         }
     }
 }
 
-impl Histogram for VecHist {
+impl<S: StorageElement, D: Buffer<S>> Histogram for VecHist<S, D> {
+    type Storage = S;
+
     fn get_axes(&self) -> &Vec<Box<dyn Axis>> {
         &self.axes
     }
 
-    fn get_bin(&self, idx: usize) -> Storage {
-        self.data[idx].clone()
+    fn get_bin(&self, idx: usize) -> S {
+        self.data.get(idx)
     }
 
-    fn fill(&mut self, indices: &Vec<usize>, weight: f32) -> Result<()> {
+    fn update_bin(&mut self, idx: usize, f: &mut dyn FnMut(&mut S)) {
+        self.data.update(idx, f)
+    }
+
+    fn empty_like(&self, axes: Vec<Box<dyn Axis>>) -> Self {
+        let dims = axes.iter().map(|axis| axis.num_bins(true)).product();
+
+        Self {
+            axes,
+            data: D::empty(dims, &self.zero),
+            zero: self.zero.clone(),
+        }
+    }
+
+    fn fill(&mut self, indices: &[usize], weight: f32) -> Result<()> {
         let axes = self.get_axes();
 
         if indices.len() != axes.len() {
@@ -54,23 +159,15 @@ impl Histogram for VecHist {
         // Find the index of the bin
         // and fill the bin with the weight
         let bin_idx = self.stride_index(indices)?;
-        match self.storage {
-            StorageType::Double => self.data[bin_idx] += Storage::Double(weight),
-            StorageType::Int => self.data[bin_idx] += Storage::Int(weight as i32),
-            StorageType::Weight => self.data[bin_idx] += Storage::Weight((weight, weight * weight)),
-        }
+        self.data.fill(bin_idx, weight)?;
 
         Ok(())
     }
 }
 
-impl Debug for VecHist {
+impl<S: StorageElement, D> Debug for VecHist<S, D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "VecHist(axes={:?}, storage={:?})",
-            self.axes, self.storage
-        )
+        write!(f, "VecHist(axes={:?}, storage={:?})", self.axes, self.zero)
         // let primitive_bins = self
         //     .data
         //     .iter()
@@ -116,12 +213,16 @@ mod tests {
         assert_eq!(hist.num_bins(true), 72);
 
         // Fill the histogram
-        let where2fill = vec![uniform.index(0.0), cat.index("A".to_string())];
+        let where2fill = vec![uniform.index(0.0), cat.index("A")];
         hist.fill(&where2fill, 1.0).unwrap();
 
         assert_eq!(hist.num_bins(false), 50);
         assert_eq!(hist.num_bins(true), 72);
         assert_eq!(hist.get_bin(0), Storage::Double(0.0));
+        // indices beyond the axis (including flow bins) are rejected
+        assert!(hist
+            .fill(&[uniform.num_bins(true), cat.index("A")], 1.0)
+            .is_err());
 
         // Check the bin
         assert_eq!(
@@ -129,4 +230,512 @@ mod tests {
             Storage::Double(1.0)
         );
     }
+
+    #[test]
+    fn test_vechist_named_axes() {
+        use hist::hist::Histogram;
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::{Storage, StorageType};
+
+        let pt = Uniform::new(10, 0.0, 10.0).unwrap().with_name("pt");
+        let dataset = Category::new(vec!["A".to_string(), "B".to_string()])
+            .unwrap()
+            .with_name("dataset");
+
+        let axes = vec![
+            Box::new(pt.clone()) as Box<dyn Axis>,
+            Box::new(dataset.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::VecHist::new(axes, StorageType::Weight);
+        assert_eq!(hist.axis_index("dataset").unwrap(), 1);
+        assert!(hist.axis_by_name("eta").is_err());
+
+        // axes can be given in any order, values are mapped to bins by each axis
+        hist.fill_by_name(&[("dataset", "A".into()), ("pt", 1.5.into())], 2.0)
+            .unwrap();
+        hist.fill_by_name(&[("pt", 1.5.into()), ("dataset", "B".into())], 1.0)
+            .unwrap();
+        hist.fill_by_name(&[("pt", 7.5.into()), ("dataset", "B".into())], 1.0)
+            .unwrap();
+        // out of range and unknown values end up in the flow bins
+        hist.fill_by_name(&[("pt", 12.0.into()), ("dataset", "C".into())], 4.0)
+            .unwrap();
+        assert_eq!(
+            hist.get_bin(
+                hist.stride_index(&[pt.overflow(), dataset.overflow()])
+                    .unwrap()
+            ),
+            Storage::Weight((4.0, 16.0))
+        );
+        assert!(hist
+            .fill_by_name(&[("pt", 0.5.into()), ("pt", 1.5.into())], 1.0)
+            .is_err());
+        // a label for a continuous axis
+        assert!(hist
+            .fill_by_name(&[("pt", "A".into()), ("dataset", "B".into())], 1.0)
+            .is_err());
+
+        // slicing drops the axis
+        let sliced = hist.slice_by_name("dataset", dataset.index("B")).unwrap();
+        assert_eq!(sliced.get_axes().len(), 1);
+        assert_eq!(sliced.get_axes()[0].name(), Some("pt"));
+        assert_eq!(sliced.get_bin(1), Storage::Weight((1.0, 1.0)));
+        assert_eq!(sliced.get_bin(7), Storage::Weight((1.0, 1.0)));
+        let sliced = hist.slice_by_name("pt", 1).unwrap();
+        assert_eq!(
+            sliced.get_bin(dataset.index("A")),
+            Storage::Weight((2.0, 4.0))
+        );
+        assert!(hist.slice_by_name("dataset", 3).is_err());
+        assert!(hist.slice_by_name("eta", 0).is_err());
+
+        // projection keeps the axis names
+        let projected = hist.project_by_name(&["pt"]).unwrap();
+        assert_eq!(projected.get_axes()[0].name(), Some("pt"));
+        assert_eq!(projected.num_bins(true), 12);
+        assert_eq!(projected.get_bin(1), Storage::Weight((3.0, 5.0)));
+        assert_eq!(projected.get_bin(7), Storage::Weight((1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_vechist_64bit_storages() {
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::{Storage, StorageType};
+
+        let x = Uniform::new(2, 0.0, 2.0).unwrap();
+        let mut hist = super::VecHist::new(vec![Box::new(x.clone())], StorageType::Double64);
+        // beyond the f32 precision limit of 2^24 counts
+        hist.data[0] = Storage::Double64(16_777_216.0);
+        hist.fill(&[x.index(0.5)], 1.0).unwrap();
+        assert_eq!(hist.get_bin(0), Storage::Double64(16_777_217.0));
+
+        let converted = hist.convert(StorageType::Int64).unwrap();
+        assert_eq!(converted.get_bin(0), Storage::Int64(16_777_217));
+        assert_eq!(converted.get_bin(1), Storage::Int64(0));
+    }
+
+    #[test]
+    fn test_vechist_regroup_integer() {
+        use hist::hist::Histogram;
+        use hist_axes::integer::Integer;
+        use hist_storages::{Storage, StorageType};
+
+        let run = Integer::new(vec![1, 2, 3, 4]).unwrap();
+        let mut hist = super::VecHist::new(vec![Box::new(run.clone())], StorageType::Double);
+        for value in [1, 2, 2, 3, 4, 4, 4] {
+            hist.fill(&[run.index(value)], 1.0).unwrap();
+        }
+        let regrouped = hist
+            .regroup(0, &[(20, vec![3, 4]), (10, vec![1, 2])])
+            .unwrap();
+        assert_eq!(regrouped.num_bins(false), 2);
+        // integer labels are sorted
+        assert_eq!(regrouped.get_bin(1), Storage::Double(3.0));
+        assert_eq!(regrouped.get_bin(2), Storage::Double(4.0));
+        assert!(hist.regroup(0, &[("a", vec!["b"])]).is_err());
+    }
+
+    #[test]
+    fn test_vechist_views() {
+        use hist::hist::Histogram;
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::StorageType;
+
+        let x = Uniform::new(2, 0.0, 2.0).unwrap();
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let axes = vec![
+            Box::new(x.clone()) as Box<dyn Axis>,
+            Box::new(cat.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::VecHist::new(axes, StorageType::Weight);
+        hist.fill(&[x.index(0.5), cat.index("b")], 2.0).unwrap();
+        hist.fill(&[x.index(0.5), cat.index("b")], 2.0).unwrap();
+        hist.fill(&[x.index(1.5), cat.index("a")], 1.0).unwrap();
+        hist.fill(&[x.index(-1.0), cat.index("c")], 3.0).unwrap();
+
+        assert_eq!(hist.shape(false), vec![2, 2]);
+        assert_eq!(hist.values(false), vec![0.0, 4.0, 1.0, 0.0]);
+        assert_eq!(hist.variances(false), vec![0.0, 8.0, 1.0, 0.0]);
+        assert_eq!(hist.counts(false), vec![0.0, 2.0, 1.0, 0.0]);
+
+        // [underflow, bins, overflow] x [bins, overflow]
+        assert_eq!(hist.shape(true), vec![4, 3]);
+        let values = hist.values(true);
+        assert_eq!(values.len(), 12);
+        assert_eq!(values[..3], [0.0, 0.0, 3.0]);
+        assert_eq!(values[3..6], [0.0, 4.0, 0.0]);
+
+        // Poisson variances without sumw2
+        let double = hist.convert(StorageType::Double).unwrap();
+        assert_eq!(double.variances(false), vec![0.0, 4.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_vechist_statistics() {
+        use hist::hist::{Histogram, Interpolation};
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::StorageType;
+
+        let x = Uniform::new(4, 0.0, 4.0).unwrap();
+        let cat = Category::new(vec!["a".to_string()]).unwrap();
+        let axes = vec![
+            Box::new(x.clone()) as Box<dyn Axis>,
+            Box::new(cat.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::VecHist::new(axes, StorageType::Double);
+        for (value, weight) in [(0.5, 1.0), (1.5, 2.0), (2.5, 3.0), (3.5, 2.0), (9.0, 5.0)] {
+            hist.fill(&[x.index(value), cat.index("a")], weight)
+                .unwrap();
+        }
+        assert_eq!(hist.sum(false), 8.0);
+        assert_eq!(hist.sum(true), 13.0);
+        assert_eq!(hist.marginal(0).unwrap(), vec![1.0, 2.0, 3.0, 2.0]);
+
+        // statistics ignore the overflow bin
+        assert_eq!(hist.mean(0).unwrap(), 2.25);
+        assert_eq!(hist.std(0).unwrap(), 0.9375f64.sqrt());
+        assert!((hist.skewness(0).unwrap() + 0.28125 / 0.9375f64.powf(1.5)).abs() < 1e-12);
+        assert_eq!(hist.median(0, Interpolation::Center).unwrap(), 2.5);
+        assert!((hist.median(0, Interpolation::Linear).unwrap() - 7.0 / 3.0).abs() < 1e-12);
+        assert_eq!(hist.quantile(0, 0.0, Interpolation::Linear).unwrap(), 0.0);
+        assert_eq!(hist.quantile(0, 1.0, Interpolation::Linear).unwrap(), 4.0);
+        assert!(hist.quantile(0, 1.5, Interpolation::Linear).is_err());
+        assert!(hist.mean(1).is_err());
+        assert!(hist.mean(2).is_err());
+    }
+
+    #[test]
+    fn test_vechist_custom_storage() {
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::{StorageElement, StorageError};
+
+        // smallest and largest weight per bin
+        #[derive(Debug, Clone, PartialEq)]
+        struct MinMax(f32, f32);
+
+        impl StorageElement for MinMax {
+            fn zero(&self) -> Self {
+                MinMax(f32::INFINITY, f32::NEG_INFINITY)
+            }
+
+            fn fill(&mut self, weight: f32) -> Result<(), StorageError> {
+                self.merge(&MinMax(weight, weight))
+            }
+
+            fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+                self.0 = self.0.min(other.0);
+                self.1 = self.1.max(other.1);
+                Ok(())
+            }
+
+            fn scale(&mut self, factor: f64) {
+                self.0 *= factor as f32;
+                self.1 *= factor as f32;
+            }
+
+            fn value(&self) -> f64 {
+                (self.1 - self.0) as f64
+            }
+
+            fn variance(&self) -> f64 {
+                0.0
+            }
+        }
+
+        let x = Uniform::new(2, 0.0, 2.0).unwrap();
+        let zero = MinMax(0.0, 0.0).zero();
+        let mut hist = super::VecHist::with_storage(vec![Box::new(x.clone())], zero);
+        for (value, weight) in [(0.5, 3.0), (0.5, -1.0), (1.5, 2.0), (1.5, 7.0)] {
+            hist.fill(&[x.index(value)], weight).unwrap();
+        }
+        assert!(hist.fill_with_sample(&[x.index(0.5)], 1.0, 1.0).is_err());
+        assert_eq!(hist.get_bin(0), MinMax(-1.0, 3.0));
+
+        let projected = hist.project(&[]).unwrap();
+        assert_eq!(projected.get_bin(0), MinMax(-1.0, 7.0));
+        assert_eq!(projected.get_bin(0).value(), 8.0);
+    }
+
+    #[test]
+    fn test_vechist_compare() {
+        use hist::compare::{ad_test, chi2_test, ks_test, pulls};
+        use hist::hist::Histogram;
+        use hist_axes::axis::Axis;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::{Storage, StorageElement, StorageType};
+
+        let x = Uniform::new(3, 0.0, 3.0).unwrap();
+        let fill = |storage: StorageType, counts: [usize; 3], weight: f32| {
+            let mut hist = super::VecHist::new(vec![Box::new(x.clone())], storage);
+            for (i, count) in counts.into_iter().enumerate() {
+                for _ in 0..count {
+                    hist.fill(&[i], weight).unwrap();
+                }
+            }
+            hist
+        };
+        let a = fill(StorageType::Double, [10, 20, 30], 1.0);
+        let b = fill(StorageType::Double, [12, 18, 30], 1.0);
+
+        let chi2 = chi2_test(&a, &b).unwrap();
+        let expected = 4.0 / 22.0 + 4.0 / 38.0;
+        assert!((chi2.statistic - expected).abs() < 1e-9);
+        assert_eq!(chi2.ndf, Some(2));
+        // two degrees of freedom: p = exp(-chi2 / 2)
+        assert!((chi2.p_value - (-expected / 2.0).exp()).abs() < 1e-9);
+        // weighted histograms with a constant weight give the same result
+        let (a2, b2) = (
+            fill(StorageType::Weight, [10, 20, 30], 2.0),
+            fill(StorageType::Weight, [12, 18, 30], 2.0),
+        );
+        assert!((chi2_test(&a2, &b2).unwrap().statistic - expected).abs() < 1e-9);
+        let mixed = chi2_test(&b, &a2).unwrap();
+        assert_eq!(mixed.ndf, Some(2));
+        assert!(mixed.p_value > 0.5);
+
+        let reversed = fill(StorageType::Double, [30, 20, 10], 1.0);
+        let ks = ks_test(&a, &reversed).unwrap();
+        assert!((ks.statistic - 1.0 / 3.0).abs() < 1e-9);
+        assert!(ks.p_value < 0.01);
+        assert!(ks_test(&a, &b).unwrap().p_value > 0.9);
+
+        let ad = ad_test(&a, &a2).unwrap();
+        assert!(ad.statistic.abs() < 1e-9);
+        assert!(ad.p_value > 0.5);
+        assert!(ad_test(&a, &reversed).unwrap().p_value < 0.01);
+        // all entries tied in a single bin
+        let single = ad_test(
+            &fill(StorageType::Double, [0, 10, 0], 1.0),
+            &fill(StorageType::Double, [0, 5, 0], 1.0),
+        )
+        .unwrap();
+        assert!(single.statistic.is_finite() && single.p_value.is_finite());
+
+        let pulls = pulls(&a, &b).unwrap();
+        assert!((pulls.get_bin(0).value() + 2.0 / 22f64.sqrt()).abs() < 1e-6);
+        assert_eq!(pulls.get_bin(2), Storage::Double(0.0));
+        assert_eq!(pulls.get_bin(x.underflow()), Storage::Double(0.0));
+
+        // only 1-D histograms with the same binning can be compared
+        let y = Uniform::new(4, 0.0, 3.0).unwrap();
+        let other = super::VecHist::new(vec![Box::new(y.clone())], StorageType::Double);
+        assert!(chi2_test(&a, &other).is_err());
+        let axes = vec![
+            Box::new(x.clone()) as Box<dyn Axis>,
+            Box::new(x.clone()) as Box<dyn Axis>,
+        ];
+        let two_d = super::VecHist::new(axes, StorageType::Double);
+        assert!(ks_test(&two_d, &two_d).is_err());
+        let empty = super::VecHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        assert!(ad_test(&a, &empty).is_err());
+    }
+
+    #[test]
+    fn test_vechist_lookup() {
+        use hist::hist::Histogram;
+        use hist::lookup::{Column, Lookup, OutOfRange};
+        use hist::sampler::Coordinate::{Continuous, Discrete};
+        use hist_axes::axis::Axis;
+        use hist_axes::bin::Value;
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_axes::variable::Variable;
+        use hist_storages::StorageType;
+
+        // scale factors 1, 2, 3 in pt and +10 for positive eta
+        let pt = Variable::new(vec![0.0, 10.0, 20.0, 40.0]).unwrap();
+        let eta = Uniform::new(2, -2.0, 2.0).unwrap();
+        let axes = vec![
+            Box::new(pt.clone()) as Box<dyn Axis>,
+            Box::new(eta.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::VecHist::new(axes, StorageType::Double);
+        for (i, value) in [5.0, 15.0, 30.0].into_iter().enumerate() {
+            hist.fill(&[pt.index(value), eta.index(-1.0)], i as f32 + 1.0)
+                .unwrap();
+            hist.fill(&[pt.index(value), eta.index(1.0)], i as f32 + 11.0)
+                .unwrap();
+        }
+        hist.fill(&[pt.index(50.0), eta.index(1.0)], 100.0).unwrap();
+
+        let lookup = Lookup::new(&hist);
+        assert_eq!(
+            lookup
+                .evaluate(&[Continuous(12.0), Continuous(-1.5)])
+                .unwrap(),
+            2.0
+        );
+        assert_eq!(
+            lookup
+                .evaluate(&[Continuous(10.0), Continuous(0.0)])
+                .unwrap(),
+            12.0
+        );
+        // clamped to the last bin
+        assert_eq!(
+            lookup
+                .evaluate(&[Continuous(50.0), Continuous(9.0)])
+                .unwrap(),
+            13.0
+        );
+        let flow = lookup.clone().with_policy(OutOfRange::Flow);
+        assert_eq!(
+            flow.evaluate(&[Continuous(50.0), Continuous(1.0)]).unwrap(),
+            100.0
+        );
+        let strict = lookup.clone().with_policy(OutOfRange::Error);
+        assert!(strict
+            .evaluate(&[Continuous(50.0), Continuous(1.0)])
+            .is_err());
+        assert!(lookup.evaluate(&[Continuous(12.0)]).is_err());
+        assert!(lookup
+            .evaluate(&[Discrete(Value::Int(1)), Continuous(1.0)])
+            .is_err());
+
+        let values = lookup
+            .evaluate_columns(&[Column::Values(&[12.0, 35.0]), Column::Values(&[-1.5, 1.5])])
+            .unwrap();
+        assert_eq!(values, vec![2.0, 13.0]);
+        assert!(lookup
+            .evaluate_columns(&[Column::Values(&[12.0]), Column::Values(&[-1.5, 1.5])])
+            .is_err());
+
+        // the lookup table has two axes
+        assert!(lookup.clone().with_interpolation(&[0, 2]).is_err());
+
+        // multilinear between bin centers, constant beyond the outermost centers
+        let smooth = lookup.with_interpolation(&[0, 1]).unwrap();
+        assert_eq!(
+            smooth
+                .evaluate(&[Continuous(10.0), Continuous(0.0)])
+                .unwrap(),
+            6.5
+        );
+        assert_eq!(
+            smooth
+                .evaluate(&[Continuous(2.0), Continuous(0.0)])
+                .unwrap(),
+            6.0
+        );
+        assert_eq!(
+            smooth
+                .evaluate(&[Continuous(35.0), Continuous(1.5)])
+                .unwrap(),
+            13.0
+        );
+        assert_eq!(
+            smooth
+                .evaluate(&[Continuous(22.5), Continuous(-1.0)])
+                .unwrap(),
+            2.5
+        );
+
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let mut hist = super::VecHist::new(vec![Box::new(cat.clone())], StorageType::Double);
+        hist.fill(&[cat.index("a")], 2.0).unwrap();
+        hist.fill(&[cat.index("c")], 5.0).unwrap();
+        let lookup = Lookup::new(&hist);
+        assert_eq!(lookup.evaluate(&[Discrete("a".into())]).unwrap(), 2.0);
+        assert!(lookup.evaluate(&[Discrete("c".into())]).is_err());
+        let flow = lookup.clone().with_policy(OutOfRange::Flow);
+        assert_eq!(flow.evaluate(&[Discrete("c".into())]).unwrap(), 5.0);
+        assert!(lookup.evaluate(&[Continuous(0.5)]).is_err());
+        assert!(lookup.with_interpolation(&[0]).is_err());
+    }
+
+    #[test]
+    fn test_vechist_fit() {
+        use hist::fit::{fit, Cost};
+        use hist::hist::Histogram;
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::StorageType;
+
+        // constant model: weighted mean with variance 1 / sum(1 / variance)
+        let x = Uniform::new(4, 0.0, 4.0).unwrap();
+        let mut hist = super::VecHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        for (i, value) in [4.0, 6.0, 5.0, 5.0].into_iter().enumerate() {
+            hist.fill(&[i], value).unwrap();
+        }
+        let constant = |_: f64, p: &[f64]| p[0];
+        let result = fit(&hist, constant, &[1.0], Cost::ChiSquare).unwrap();
+        let inverse_sum = 1.0 / 4.0 + 1.0 / 6.0 + 2.0 / 5.0;
+        assert!(result.converged);
+        assert!((result.parameters[0] - 4.0 / inverse_sum).abs() < 1e-6);
+        assert!((result.covariance[0][0] - 1.0 / inverse_sum).abs() < 1e-6);
+        assert_eq!(result.ndf, 3);
+        // Poisson likelihood: the mean with variance mean / n
+        let result = fit(&hist, constant, &[1.0], Cost::Poisson).unwrap();
+        assert!((result.parameters[0] - 5.0).abs() < 1e-6);
+        assert!((result.errors[0] - 1.25f64.sqrt()).abs() < 1e-6);
+
+        // Gaussian peak on an exponential background, exact expectations
+        let x = Uniform::new(40, 0.0, 10.0).unwrap();
+        let model = |x: f64, p: &[f64]| {
+            p[0] * (-0.5 * ((x - p[1]) / p[2]).powi(2)).exp() + p[3] * (-p[4] * x).exp()
+        };
+        let truth = [200.0, 4.0, 0.8, 100.0, 0.3];
+        let mut hist = super::VecHist::new(vec![Box::new(x.clone())], StorageType::Weight);
+        for (i, center) in x.centers().into_iter().enumerate() {
+            hist.fill(&[i], model(center, &truth) as f32).unwrap();
+        }
+        let start = [150.0, 3.5, 1.0, 80.0, 0.2];
+        for cost in [Cost::ChiSquare, Cost::Poisson] {
+            let result = fit(&hist, model, &start, cost).unwrap();
+            assert!(result.converged);
+            for (p, t) in result.parameters.iter().zip(truth.iter()) {
+                assert!((p - t).abs() < 1e-3 * t, "{cost:?}: {p} != {t}");
+            }
+            assert!(result.chi2 < 1e-6);
+            assert_eq!(result.ndf, 35);
+            assert!(result.p_value > 0.99);
+            assert!(result.errors.iter().all(|e| e.is_finite() && *e > 0.0));
+        }
+
+        // empty bins are skipped in the chi-square
+        let empty = super::VecHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        assert!(fit(&empty, constant, &[1.0], Cost::ChiSquare).is_err());
+        assert!(fit(&hist, constant, &[-1.0], Cost::Poisson).is_err());
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let hist = super::VecHist::new(vec![Box::new(cat)], StorageType::Double);
+        assert!(fit(&hist, constant, &[1.0], Cost::ChiSquare).is_err());
+    }
+
+    #[test]
+    fn test_vechist_unlimited() {
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+
+        let axis1 = Uniform::new(10, 0.0, 10.0).unwrap();
+        let axis2 = Uniform::new(10, 0.0, 10.0).unwrap();
+        let axes = vec![
+            Box::new(axis1.clone()) as Box<dyn Axis>,
+            Box::new(axis2.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::VecHist::unlimited(axes);
+        assert_eq!(hist.data.len(), 144);
+        assert_eq!(hist.data.element_size(), 1);
+
+        let where2fill = vec![axis1.index(0.5), axis2.index(0.5)];
+        for _ in 0..300 {
+            hist.fill(&where2fill, 1.0).unwrap();
+        }
+        assert_eq!(hist.data.element_size(), 2);
+        let bin_idx = hist.stride_index(&where2fill).unwrap();
+        assert_eq!(hist.get_bin(bin_idx), 300.0);
+
+        hist.fill(&where2fill, 0.25).unwrap();
+        assert_eq!(hist.data.element_size(), 8);
+        assert_eq!(hist.get_bin(bin_idx), 300.25);
+
+        // projections and merges go through `update_bin` and keep the packed buffer
+        let projected = hist.project(&[0]).unwrap();
+        assert_eq!(projected.get_bin(0), 300.25);
+        assert_eq!(projected.data.element_size(), 8);
+    }
 }
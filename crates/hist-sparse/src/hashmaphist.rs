@@ -1,40 +1,83 @@
 use anyhow::Result;
-use hist::hist::{HistError, Histogram};
+use hist::hist::{variation_of, HistError, Histogram};
 use hist_axes::axis::Axis;
-use hist_storages::{Storage, StorageType};
+use hist_storages::multiweight::MultiWeight;
+use hist_storages::{Storage, StorageElement, StorageError, StorageType};
 use std::collections::HashMap;
 
 // Holds the data as a hashmap
-pub struct HashMapHist {
+pub struct HashMapHist<S = Storage> {
     pub axes: Vec<Box<dyn Axis>>,
-    pub data: HashMap<usize, Storage>,
-    pub storage: StorageType,
+    pub data: HashMap<usize, S>,
+    pub zero: S,
 }
 
 impl HashMapHist {
     pub fn new(axes: Vec<Box<dyn Axis>>, storage: StorageType) -> Self {
+        Self::with_storage(axes, storage.zero())
+    }
+
+    // copy of the histogram with bins converted to another storage type
+    pub fn convert(&self, storage: StorageType) -> Result<Self> {
+        Ok(Self {
+            axes: self.axes.clone(),
+            data: self
+                .data
+                .iter()
+                .map(|(&idx, bin)| Ok((idx, bin.convert(&storage)?)))
+                .collect::<Result<HashMap<usize, Storage>, StorageError>>()?,
+            zero: storage.zero(),
+        })
+    }
+}
+
+impl HashMapHist<MultiWeight> {
+    // variation `k` as a standalone histogram with `Weight` storage
+    // (`Double` if the multi-weight storage keeps no sums of squared weights)
+    pub fn variation(&self, k: usize) -> Result<HashMapHist> {
+        let empty = HashMapHist::with_storage(self.axes.clone(), self.zero.variation(k)?);
+        variation_of(self, k, empty)
+    }
+}
+
+impl<S: StorageElement> HashMapHist<S> {
+    // histogram with any storage, `zero` is the content of an empty bin
+    pub fn with_storage(axes: Vec<Box<dyn Axis>>, zero: S) -> Self {
         Self {
             axes,
             data: HashMap::new(),
-            storage,
+            zero,
         }
     }
 }
 
-impl Histogram for HashMapHist {
+impl<S: StorageElement> Histogram for HashMapHist<S> {
+    type Storage = S;
+
     fn get_axes(&self) -> &Vec<Box<dyn Axis>> {
         &self.axes
     }
 
-    fn get_bin(&self, idx: usize) -> Storage {
-        self.data.get(&idx).cloned().unwrap_or(match self.storage {
-            StorageType::Double => Storage::Double(0.0),
-            StorageType::Int => Storage::Int(0),
-            StorageType::Weight => Storage::Weight((0.0, 0.0)),
-        })
+    fn get_bin(&self, idx: usize) -> S {
+        self.data
+            .get(&idx)
+            .cloned()
+            .unwrap_or_else(|| self.zero.clone())
     }
 
-    fn fill(&mut self, indices: &Vec<usize>, weight: f32) -> Result<()> {
+    fn update_bin(&mut self, idx: usize, f: &mut dyn FnMut(&mut S)) {
+        f(self.data.entry(idx).or_insert_with(|| self.zero.clone()))
+    }
+
+    fn iter_bins(&self) -> Box<dyn Iterator<Item = (usize, S)> + '_> {
+        Box::new(self.data.iter().map(|(&idx, bin)| (idx, bin.clone())))
+    }
+
+    fn empty_like(&self, axes: Vec<Box<dyn Axis>>) -> Self {
+        Self::with_storage(axes, self.zero.clone())
+    }
+
+    fn fill(&mut self, indices: &[usize], weight: f32) -> Result<()> {
         let axes = self.get_axes();
 
         if indices.len() != axes.len() {
@@ -52,24 +95,14 @@ impl Histogram for HashMapHist {
         // if the bin exists: increment the bin inplace
         // otherwise: insert the bin
         match self.data.get_mut(&bin_idx) {
-            Some(val) => {
-                match self.storage {
-                    StorageType::Double => *val += Storage::Double(weight),
-                    StorageType::Int => *val += Storage::Int(weight as i32),
-                    StorageType::Weight => *val += Storage::Weight((weight, weight * weight)),
-                }
-                Ok(())
-            }
+            Some(val) => val.fill(weight)?,
             None => {
-                let init_val = match self.storage {
-                    StorageType::Double => Storage::Double(weight),
-                    StorageType::Int => Storage::Int(weight as i32),
-                    StorageType::Weight => Storage::Weight((weight, weight * weight)),
-                };
-                self.data.insert(bin_idx, init_val);
-                Ok(())
+                let mut bin = self.zero.clone();
+                bin.fill(weight)?;
+                self.data.insert(bin_idx, bin);
             }
         }
+        Ok(())
     }
 }
 
@@ -99,4 +132,259 @@ mod tests {
         assert_eq!(hist.get_bin(0), Storage::Double(1.0));
         assert_eq!(hist.get_bin(1), Storage::Double(0.0));
     }
+
+    #[test]
+    fn test_hashmaphist_profile() {
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::StorageType;
+
+        let x = Uniform::new(10, 0.0, 10.0).unwrap();
+        let mut hist = super::HashMapHist::new(vec![Box::new(x.clone())], StorageType::Mean);
+        for (value, sample) in [(0.5, 1.0), (0.5, 3.0), (1.5, 10.0)] {
+            hist.fill_with_sample(&[x.index(value)], sample, 1.0)
+                .unwrap();
+        }
+        // mean storages need a sample
+        assert!(hist.fill(&[x.index(0.5)], 1.0).is_err());
+
+        let bin = hist.get_bin(x.index(0.5));
+        assert_eq!(bin.mean(), Some(2.0));
+        assert_eq!(bin.std_error(), Some(1.0));
+        assert_eq!(hist.get_bin(x.index(1.5)).mean(), Some(10.0));
+
+        let mut counts = super::HashMapHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        assert!(counts.fill_with_sample(&[x.index(0.5)], 1.0, 1.0).is_err());
+        // a failed update does not leave an empty bin behind
+        assert!(counts.data.is_empty());
+    }
+
+    #[test]
+    fn test_hashmaphist_merge() {
+        use hist::hist::Histogram;
+        use hist_axes::axis::Axis;
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::{Storage, StorageType};
+
+        let x = Uniform::new(10, 0.0, 10.0).unwrap().with_name("x");
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let reordered = Category::new(vec!["b".to_string(), "a".to_string()]).unwrap();
+
+        let mut hist = super::HashMapHist::new(
+            vec![Box::new(x.clone()), Box::new(cat.clone())],
+            StorageType::Double,
+        );
+        let mut other = super::HashMapHist::new(
+            vec![Box::new(x.clone()), Box::new(reordered.clone())],
+            StorageType::Double,
+        );
+        hist.fill(&[x.index(2.5), cat.index("a")], 1.0).unwrap();
+        other
+            .fill(&[x.index(2.5), reordered.index("a")], 2.0)
+            .unwrap();
+        other
+            .fill(&[x.index(2.5), reordered.index("b")], 4.0)
+            .unwrap();
+
+        hist.merge(&other).unwrap();
+        let a = hist.stride_index(&[x.index(2.5), cat.index("a")]).unwrap();
+        let b = hist.stride_index(&[x.index(2.5), cat.index("b")]).unwrap();
+        assert_eq!(hist.get_bin(a), Storage::Double(3.0));
+        assert_eq!(hist.get_bin(b), Storage::Double(4.0));
+
+        // the error names the offending axis
+        let wrong = super::HashMapHist::new(
+            vec![
+                Box::new(Uniform::new(5, 0.0, 10.0).unwrap().with_name("x")) as Box<dyn Axis>,
+                Box::new(cat.clone()),
+            ],
+            StorageType::Double,
+        );
+        let err = hist.merge(&wrong).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "axis 0 ('x') mismatch: number of bins differs (10 vs 5)"
+        );
+    }
+
+    #[test]
+    fn test_hashmaphist_multiweight() {
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::multiweight::MultiWeight;
+        use hist_storages::{Storage, StorageType};
+
+        let x = Uniform::new(4, 0.0, 4.0).unwrap();
+        let mut hist =
+            super::HashMapHist::with_storage(vec![Box::new(x.clone())], MultiWeight::new(3, true));
+        // one bin lookup fills the nominal weight and both variations
+        hist.fill_weights(&[x.index(1.5)], &[1.0, 1.5, 0.5])
+            .unwrap();
+        hist.fill_weights(&[x.index(1.5)], &[2.0, 2.5, 1.5])
+            .unwrap();
+        hist.fill(&[x.index(2.5)], 1.0).unwrap();
+        assert!(hist.fill_weights(&[x.index(2.5)], &[1.0]).is_err());
+
+        let up = hist.variation(1).unwrap();
+        assert_eq!(up.get_bin(x.index(1.5)), Storage::Weight((4.0, 8.5)));
+        assert_eq!(up.get_bin(x.index(2.5)), Storage::Weight((1.0, 1.0)));
+        assert_eq!(up.get_bin(x.index(0.5)), Storage::Weight((0.0, 0.0)));
+        assert!(hist.variation(3).is_err());
+
+        let mut counts = super::HashMapHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        assert!(counts.fill_weights(&[x.index(1.5)], &[1.0, 2.0]).is_err());
+    }
+
+    #[test]
+    fn test_hashmaphist_efficiency() {
+        use hist::efficiency::{clopper_pearson, Efficiency, IntervalMethod};
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::StorageType;
+
+        let x = Uniform::new(2, 0.0, 2.0).unwrap();
+        let new = || super::HashMapHist::new(vec![Box::new(x.clone())], StorageType::Weight);
+        let (mut passed, mut total) = (new(), new());
+        for i in 0..10 {
+            total.fill(&[x.index(0.5)], 1.0).unwrap();
+            if i < 3 {
+                passed.fill(&[x.index(0.5)], 1.0).unwrap();
+            }
+            // weighted bin: 4 of 10 entries with weight 2 pass
+            total.fill(&[x.index(1.5)], 2.0).unwrap();
+            if i < 4 {
+                passed.fill(&[x.index(1.5)], 2.0).unwrap();
+            }
+        }
+
+        let efficiency = Efficiency::new(&passed, &total, false).unwrap();
+        assert_eq!(efficiency.values(), vec![0.3, 0.4]);
+        // equal weights: the effective number of entries is the number of entries
+        assert_eq!(efficiency.entries, vec![10.0, 10.0]);
+        let intervals = efficiency
+            .intervals(IntervalMethod::ClopperPearson, 0.68)
+            .unwrap();
+        assert_eq!(intervals[0], clopper_pearson(3.0, 10.0, 0.68));
+        assert!((intervals[1].0 - clopper_pearson(4.0, 10.0, 0.68).0).abs() < 1e-12);
+        let wilson = efficiency.intervals(IntervalMethod::Wilson, 0.68).unwrap();
+        assert!(wilson[0].0 < 0.3 && 0.3 < wilson[0].1);
+        assert!(efficiency.intervals(IntervalMethod::Wilson, 1.0).is_err());
+
+        // flow bins are empty
+        let with_flow = Efficiency::new(&passed, &total, true).unwrap();
+        assert!(with_flow.values()[0].is_nan());
+        let bayesian = IntervalMethod::Bayesian {
+            alpha: 1.0,
+            beta: 1.0,
+        };
+        assert_eq!(with_flow.intervals(bayesian, 0.68).unwrap()[0], (0.0, 1.0));
+        let improper = IntervalMethod::Bayesian {
+            alpha: 0.0,
+            beta: 1.0,
+        };
+        assert!(efficiency.intervals(improper, 0.68).is_err());
+
+        assert!(Efficiency::new(&total, &passed, false).is_err());
+        let other = super::HashMapHist::new(
+            vec![Box::new(Uniform::new(3, 0.0, 2.0).unwrap())],
+            StorageType::Weight,
+        );
+        assert!(Efficiency::new(&passed, &other, false).is_err());
+    }
+
+    #[test]
+    fn test_hashmaphist_unfold() {
+        use hist::hist::Histogram;
+        use hist::unfold::Response;
+        use hist_axes::axis::Axis;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::StorageType;
+
+        let reco = Uniform::new(3, 0.0, 3.0).unwrap();
+        let truth = Uniform::new(3, 0.0, 3.0).unwrap();
+        let axes = vec![
+            Box::new(reco.clone()) as Box<dyn Axis>,
+            Box::new(truth.clone()) as Box<dyn Axis>,
+        ];
+        // 1000 events per truth bin: 80% in the same reco bin, 10% in each neighbour,
+        // events migrating out of the reco range are misses; 50 fakes per reco bin
+        let mut response =
+            Response::new(super::HashMapHist::new(axes, StorageType::Weight)).unwrap();
+        for i in 0..3 {
+            response.fill(i, i, 800.0).unwrap();
+            for j in [i.wrapping_sub(1), i + 1] {
+                match j {
+                    0..=2 => response.fill(j, i, 100.0).unwrap(),
+                    _ => response.fill_miss(i, 100.0).unwrap(),
+                }
+            }
+            response.fill_fake(i, 50.0).unwrap();
+        }
+        let matrix = response.matrix().unwrap();
+        assert_eq!(matrix.truth, vec![1000.0; 3]);
+        assert_eq!(matrix.efficiencies, vec![0.9, 1.0, 0.9]);
+        assert_eq!(matrix.probabilities[1], vec![0.1, 0.8, 0.1]);
+        assert_eq!(matrix.fake_fractions[0], 50.0 / 950.0);
+
+        // data: folded truth spectrum plus fakes
+        let data = |spectrum: [f64; 3]| {
+            let mut data =
+                super::HashMapHist::new(vec![Box::new(reco.clone())], StorageType::Double);
+            for j in 0..3 {
+                let signal: f64 = (0..3)
+                    .map(|i| matrix.probabilities[j][i] * spectrum[i])
+                    .sum();
+                data.fill(&[j], (signal / (1.0 - matrix.fake_fractions[j])) as f32)
+                    .unwrap();
+            }
+            data
+        };
+
+        let spectrum = [1000.0, 2000.0, 500.0];
+        let unfolded = response.bayes(&data(spectrum), 200).unwrap();
+        for (value, expected) in unfolded.values.iter().zip(spectrum.iter()) {
+            assert!((value - expected).abs() < 1e-2 * expected);
+        }
+        assert!((unfolded.hist.values(false)[1] - unfolded.values[1]).abs() < 1e-2);
+        // the bins carry the diagonal of the covariance, not value^2
+        let variance = unfolded.hist.variances(false)[1];
+        assert!((variance - unfolded.covariance[1][1]).abs() < 1e-3 * variance);
+        assert!(unfolded.covariance[0][0] > 0.0);
+        assert_eq!(unfolded.covariance[0][1], unfolded.covariance[1][0]);
+        // neighbouring bins are anti-correlated after unfolding
+        assert!(unfolded.covariance[0][1] < 0.0);
+
+        // the curvature of the ratio to the response truth spectrum vanishes
+        let spectrum = [2000.0; 3];
+        let weak = response.svd(&data(spectrum), 3).unwrap();
+        let strong = response.svd(&data(spectrum), 2).unwrap();
+        for unfolded in [&weak, &strong] {
+            for value in &unfolded.values {
+                assert!((value - 2000.0).abs() < 1e-2 * 2000.0);
+            }
+        }
+        assert!(strong.covariance[0][0] < weak.covariance[0][0]);
+        // tau = s_1^2 also halves the leading (flat) component
+        let flat = response.svd(&data(spectrum), 1).unwrap();
+        assert!((flat.values[0] - 1000.0).abs() < 1.0);
+
+        assert!(response.bayes(&data(spectrum), 0).is_err());
+        assert!(response.svd(&data(spectrum), 4).is_err());
+        let other = super::HashMapHist::new(
+            vec![Box::new(Uniform::new(3, 0.0, 6.0).unwrap())],
+            StorageType::Double,
+        );
+        assert!(response.bayes(&other, 1).is_err());
+        let one_d = super::HashMapHist::new(vec![Box::new(reco.clone())], StorageType::Double);
+        assert!(Response::new(one_d).is_err());
+        // unfolded values cannot be stored in integer or profile bins
+        for storage in [StorageType::Int, StorageType::Mean] {
+            let axes = vec![
+                Box::new(reco.clone()) as Box<dyn Axis>,
+                Box::new(truth.clone()) as Box<dyn Axis>,
+            ];
+            assert!(Response::new(super::HashMapHist::new(axes, storage)).is_err());
+        }
+    }
 }
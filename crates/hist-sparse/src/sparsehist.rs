@@ -1,45 +1,99 @@
 use anyhow::Result;
-use hist::hist::{HistError, Histogram};
+use hist::hist::{variation_of, HistError, Histogram};
 use hist_axes::axis::Axis;
-use hist_storages::{Storage, StorageType};
+use hist_storages::multiweight::MultiWeight;
+use hist_storages::{Storage, StorageElement, StorageError, StorageType};
 
 // Holds the data as a Vec of filled bins
 #[derive(Debug)]
-pub struct SparseHist {
+pub struct SparseHist<S = Storage> {
     pub axes: Vec<Box<dyn Axis>>,
-    pub data: Vec<Storage>,
+    pub data: Vec<S>,
     pub data_indices: Vec<usize>,
-    pub storage: StorageType,
+    pub zero: S,
 }
 
 impl SparseHist {
     pub fn new(axes: Vec<Box<dyn Axis>>, storage: StorageType) -> Self {
+        Self::with_storage(axes, storage.zero())
+    }
+
+    // copy of the histogram with bins converted to another storage type
+    pub fn convert(&self, storage: StorageType) -> Result<Self> {
+        Ok(Self {
+            axes: self.axes.clone(),
+            data: self
+                .data
+                .iter()
+                .map(|bin| bin.convert(&storage))
+                .collect::<Result<Vec<Storage>, StorageError>>()?,
+            data_indices: self.data_indices.clone(),
+            zero: storage.zero(),
+        })
+    }
+}
+
+impl SparseHist<MultiWeight> {
+    // variation `k` as a standalone histogram with `Weight` storage
+    // (`Double` if the multi-weight storage keeps no sums of squared weights)
+    pub fn variation(&self, k: usize) -> Result<SparseHist> {
+        let empty = SparseHist::with_storage(self.axes.clone(), self.zero.variation(k)?);
+        variation_of(self, k, empty)
+    }
+}
+
+impl<S: StorageElement> SparseHist<S> {
+    // histogram with any storage, `zero` is the content of an empty bin
+    pub fn with_storage(axes: Vec<Box<dyn Axis>>, zero: S) -> Self {
         Self {
             axes,
             data: Vec::new(),         // keeps track of the values of filled bins
             data_indices: Vec::new(), // keeps track of the indices of filled bins
-            storage,
+            zero,
         }
     }
 }
 
-impl Histogram for SparseHist {
+impl<S: StorageElement> Histogram for SparseHist<S> {
+    type Storage = S;
+
     fn get_axes(&self) -> &Vec<Box<dyn Axis>> {
         &self.axes
     }
 
-    fn get_bin(&self, idx: usize) -> Storage {
-        self.data_indices.iter().position(|&x| x == idx).map_or(
-            match self.storage {
-                StorageType::Double => Storage::Double(0.0),
-                StorageType::Int => Storage::Int(0),
-                StorageType::Weight => Storage::Weight((0.0, 0.0)),
-            },
-            |pos| self.data[pos].clone(),
+    fn get_bin(&self, idx: usize) -> S {
+        self.data_indices
+            .iter()
+            .position(|&x| x == idx)
+            .map_or_else(|| self.zero.clone(), |pos| self.data[pos].clone())
+    }
+
+    fn update_bin(&mut self, idx: usize, f: &mut dyn FnMut(&mut S)) {
+        match self.data_indices.iter().position(|&x| x == idx) {
+            Some(pos) => f(&mut self.data[pos]),
+            None => {
+                let mut bin = self.zero.clone();
+                f(&mut bin);
+                self.data_indices.push(idx);
+                self.data.push(bin);
+            }
+        }
+    }
+
+    fn iter_bins(&self) -> Box<dyn Iterator<Item = (usize, S)> + '_> {
+        Box::new(
+            self.data_indices
+                .iter()
+                .copied()
+                .zip(self.data.iter().cloned()),
         )
     }
 
-    fn fill(&mut self, indices: &Vec<usize>, weight: f32) -> Result<()> {
+    fn empty_like(&self, axes: Vec<Box<dyn Axis>>) -> Self {
+        Self::with_storage(axes, self.zero.clone())
+    }
+
+    fn fill(&mut self, indices: &[usize], weight: f32) -> Result<()> {
         let axes = self.get_axes();
 
         if indices.len() != axes.len() {
@@ -57,21 +111,12 @@ impl Histogram for SparseHist {
         // if the bin exists: increment the bin inplace
         // otherwise: push the bin to the data and data_indices vecs
         if let Some(idx) = self.data_indices.iter().position(|&x| x == bin_idx) {
-            match &mut self.data[idx] {
-                Storage::Double(val) => *val += weight,
-                Storage::Int(val) => *val += weight as i32,
-                Storage::Weight(val) => {
-                    val.0 += weight;
-                    val.1 += weight * weight;
-                }
-            }
+            self.data[idx].fill(weight)?;
         } else {
+            let mut bin = self.zero.clone();
+            bin.fill(weight)?;
             self.data_indices.push(bin_idx);
-            match self.storage {
-                StorageType::Double => self.data.push(Storage::Double(weight)),
-                StorageType::Int => self.data.push(Storage::Int(weight as i32)),
-                StorageType::Weight => self.data.push(Storage::Weight((weight, weight * weight))),
-            }
+            self.data.push(bin);
         }
 
         Ok(())
@@ -106,4 +151,200 @@ mod tests {
 
         assert_eq!(hist.get_bin(hist.data_indices[0]), Storage::Double(1.0));
     }
+
+    #[test]
+    fn test_sparsehist_regroup() {
+        use hist::hist::Histogram;
+        use hist_axes::axis::Axis;
+        use hist_axes::category::Category;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::{Storage, StorageType};
+
+        let x = Uniform::new(10, 0.0, 10.0).unwrap();
+        let datasets = ["ttbar_had", "ttbar_lep", "wjets", "data"];
+        let dataset = Category::new(datasets.iter().map(|d| d.to_string()).collect())
+            .unwrap()
+            .with_name("dataset");
+        let axes = vec![
+            Box::new(x.clone()) as Box<dyn Axis>,
+            Box::new(dataset.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::SparseHist::new(axes, StorageType::Weight);
+        for (name, weight) in datasets.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            hist.fill(&[x.index(0.5), dataset.index(name)], weight)
+                .unwrap();
+        }
+
+        let regrouped = hist
+            .regroup(
+                1,
+                &[
+                    ("ttbar", vec!["ttbar_had", "ttbar_lep"]),
+                    ("wjets", vec!["wjets"]),
+                ],
+            )
+            .unwrap();
+        let process = regrouped.get_axes()[1].as_ref();
+        assert_eq!(process.num_bins(false), 2);
+        assert_eq!(process.name(), Some("dataset"));
+        let ttbar = regrouped.stride_index(&[x.index(0.5), 1]).unwrap();
+        assert_eq!(regrouped.get_bin(ttbar), Storage::Weight((3.0, 5.0)));
+        // "data" is not part of any group and ends up in the overflow bin
+        let overflow = regrouped
+            .stride_index(&[x.index(0.5), process.overflow()])
+            .unwrap();
+        assert_eq!(regrouped.get_bin(overflow), Storage::Weight((4.0, 16.0)));
+
+        assert!(hist.regroup(1, &[("ttbar", vec!["tt"])]).is_err());
+        assert!(hist
+            .regroup(1, &[("a", vec!["wjets"]), ("b", vec!["wjets"])])
+            .is_err());
+        assert!(hist.regroup(0, &[("a", vec!["wjets"])]).is_err());
+    }
+
+    #[test]
+    fn test_sparsehist_bootstrap() {
+        use hist::hist::Histogram;
+        use hist_axes::uniform::Uniform;
+        use hist_storages::bootstrap::Bootstrap;
+        use hist_storages::StorageElement;
+
+        let x = Uniform::new(2, 0.0, 2.0).unwrap();
+        let zero = Bootstrap::new(100);
+        let mut pt = super::SparseHist::with_storage(vec![Box::new(x.clone())], zero.clone());
+        let mut eta = super::SparseHist::with_storage(vec![Box::new(x.clone())], zero);
+        for event in 0..500u64 {
+            pt.fill_with_seed(&[x.index(0.5)], 1.0, event).unwrap();
+            // every second event also ends up in `eta`
+            if event % 2 == 0 {
+                eta.fill_with_seed(&[x.index(1.5)], 1.0, event).unwrap();
+            }
+        }
+        assert!(pt.fill(&[x.index(0.5)], 1.0).is_err());
+        // a failed update does not leave an empty bin behind
+        assert!(eta.fill_with_sample(&[x.index(0.5)], 1.0, 1.0).is_err());
+        assert_eq!(eta.data_indices, vec![x.index(1.5)]);
+
+        let (a, b) = (pt.get_bin(x.index(0.5)), eta.get_bin(x.index(1.5)));
+        assert_eq!(a.value(), 500.0);
+        assert_eq!(b.value(), 250.0);
+        // shared events: cov(a, b) = var(b)
+        let corr = a.covariance(&b).unwrap() / (a.variance() * b.variance()).sqrt();
+        assert!((corr - 0.5f64.sqrt()).abs() < 0.15);
+    }
+
+    #[test]
+    fn test_sparsehist_density_cumulative() {
+        use hist::hist::{Direction, Histogram};
+        use hist_axes::axis::Axis;
+        use hist_axes::category::Category;
+        use hist_axes::variable::Variable;
+        use hist_storages::{Storage, StorageType};
+
+        let x = Variable::new(vec![0.0, 1.0, 3.0, 4.0]).unwrap();
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let axes = vec![
+            Box::new(x.clone()) as Box<dyn Axis>,
+            Box::new(cat.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::SparseHist::new(axes, StorageType::Weight);
+        for (value, label, weight) in [
+            (0.5, "a", 2.0),
+            (2.0, "a", 2.0),
+            (3.5, "b", 4.0),
+            (-1.0, "a", 1.0),
+            (5.0, "b", 3.0),
+        ] {
+            hist.fill(&[x.index(value), cat.index(label)], weight)
+                .unwrap();
+        }
+        let bin = |h: &super::SparseHist, value: f32, label: &str| {
+            h.get_bin(h.stride_index(&[x.index(value), cat.index(label)]).unwrap())
+        };
+
+        // the total excludes flow bins, bins are divided by their width,
+        // (x, category) row-major over the regular bins
+        let (density, variances) = hist.density().unwrap();
+        assert_eq!(density, vec![0.25, 0.0, 0.125, 0.0, 0.0, 0.5]);
+        assert_eq!(variances, vec![0.0625, 0.0, 0.015625, 0.0, 0.0, 0.25]);
+
+        let forward = hist.cumulative(0, Direction::Forward, false).unwrap();
+        assert_eq!(bin(&forward, 2.0, "a"), Storage::Weight((4.0, 8.0)));
+        assert_eq!(bin(&forward, 3.5, "a"), Storage::Weight((4.0, 8.0)));
+        assert_eq!(bin(&forward, -1.0, "a"), Storage::Weight((1.0, 1.0)));
+
+        let backward = hist.cumulative(0, Direction::Backward, true).unwrap();
+        assert_eq!(bin(&backward, 3.5, "b"), Storage::Weight((7.0, 25.0)));
+        assert_eq!(bin(&backward, 0.5, "a"), Storage::Weight((4.0, 8.0)));
+        assert_eq!(bin(&backward, -1.0, "a"), Storage::Weight((5.0, 9.0)));
+
+        // only lines with filled bins are summed, from their first filled bin on
+        assert_eq!(forward.iter_bins().count(), 6);
+
+        let empty = super::SparseHist::new(vec![Box::new(x.clone())], StorageType::Weight);
+        assert!(empty.density().is_err());
+
+        // Poisson variances for counting storages: 4 entries in a bin of width 2
+        for storage in [StorageType::Double, StorageType::Int] {
+            let mut counts = super::SparseHist::new(vec![Box::new(x.clone())], storage);
+            counts.fill(&[x.index(2.0)], 4.0).unwrap();
+            let (density, variances) = counts.density().unwrap();
+            assert_eq!(density, vec![0.0, 0.5, 0.0]);
+            assert_eq!(variances, vec![0.0, 0.0625, 0.0]);
+        }
+    }
+
+    #[test]
+    fn test_sparsehist_sampler() {
+        use hist::hist::Histogram;
+        use hist::sampler::{Coordinate, Sampler};
+        use hist_axes::axis::Axis;
+        use hist_axes::bin::Value;
+        use hist_axes::category::Category;
+        use hist_axes::variable::Variable;
+        use hist_storages::StorageType;
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        let x = Variable::new(vec![0.0, 1.0, 3.0]).unwrap();
+        let cat = Category::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let axes = vec![
+            Box::new(x.clone()) as Box<dyn Axis>,
+            Box::new(cat.clone()) as Box<dyn Axis>,
+        ];
+        let mut hist = super::SparseHist::new(axes, StorageType::Double);
+        hist.fill(&[x.index(0.5), cat.index("a")], 1.0).unwrap();
+        hist.fill(&[x.index(2.0), cat.index("b")], 3.0).unwrap();
+        // flow bins are never sampled
+        hist.fill(&[x.index(-1.0), cat.index("a")], 100.0).unwrap();
+
+        let sampler = Sampler::new(&hist).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        let toys = sampler.sample_n(&mut rng, 4000);
+        let mut in_b = 0;
+        for toy in &toys {
+            match toy.as_slice() {
+                [Coordinate::Continuous(v), Coordinate::Discrete(Value::Str(label))] => {
+                    if label == "a" {
+                        assert!((0.0..1.0).contains(v));
+                    } else {
+                        assert!((1.0..3.0).contains(v));
+                        in_b += 1;
+                    }
+                }
+                _ => panic!("unexpected coordinates {toy:?}"),
+            }
+        }
+        assert!((in_b as f64 / 4000.0 - 0.75).abs() < 0.03);
+
+        // same seed, same toys
+        let mut rng = SmallRng::seed_from_u64(1);
+        assert_eq!(sampler.sample_n(&mut rng, 4000), toys);
+
+        let empty = super::SparseHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        assert!(Sampler::new(&empty).is_err());
+        let mut negative = super::SparseHist::new(vec![Box::new(x.clone())], StorageType::Double);
+        negative.fill(&[x.index(0.5)], -1.0).unwrap();
+        assert!(Sampler::new(&negative).is_err());
+    }
 }
@@ -0,0 +1,198 @@
+use crate::{StorageElement, StorageError};
+
+// Poisson bootstrap replicas (like ROOT's `TH1Bootstrap` or the `bootstrap_histogram` package):
+// - every entry is added to the nominal sum and with a Poisson(1) multiplier to each replica
+// - the multipliers are derived from a per-event `seed`, so the same event gets the same
+//   multipliers in all histograms and the replicas keep the correlations between them
+// - the spread of the replicas estimates the statistical uncertainty of the nominal sum
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bootstrap {
+    pub sumw: f32,
+    pub replicas: Vec<f32>,
+}
+
+impl Bootstrap {
+    pub fn new(size: usize) -> Self {
+        Self {
+            sumw: 0.0,
+            replicas: vec![0.0; size],
+        }
+    }
+
+    // number of replicas
+    pub fn len(&self) -> usize {
+        self.replicas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+
+    // covariance of two bins estimated from their replicas,
+    // the bins need to be filled with the same event seeds to be correlated
+    pub fn covariance(&self, other: &Bootstrap) -> Result<f64, StorageError> {
+        if other.len() != self.len() {
+            return Err(StorageError::WeightsMismatch {
+                expected: self.len(),
+                got: other.len(),
+            });
+        }
+        if self.len() < 2 {
+            return Ok(0.0);
+        }
+        let (mean, other_mean) = (self.replica_mean(), other.replica_mean());
+        let sum: f64 = self
+            .replicas
+            .iter()
+            .zip(&other.replicas)
+            .map(|(&a, &b)| (a as f64 - mean) * (b as f64 - other_mean))
+            .sum();
+        Ok(sum / (self.len() - 1) as f64)
+    }
+
+    fn replica_mean(&self) -> f64 {
+        self.replicas.iter().map(|&r| r as f64).sum::<f64>() / self.len() as f64
+    }
+}
+
+// covariance matrix of `bins` (e.g. all bins of a histogram) estimated from their replicas
+pub fn covariance_matrix(bins: &[Bootstrap]) -> Result<Vec<Vec<f64>>, StorageError> {
+    let mut matrix = vec![vec![0.0; bins.len()]; bins.len()];
+    for i in 0..bins.len() {
+        for j in 0..=i {
+            let cov = bins[i].covariance(&bins[j])?;
+            matrix[i][j] = cov;
+            matrix[j][i] = cov;
+        }
+    }
+    Ok(matrix)
+}
+
+// Poisson(1) multipliers of the first `size` replicas for the event `seed`
+pub fn multipliers(seed: u64, size: usize) -> impl Iterator<Item = u32> {
+    let mut rng = SplitMix64(seed);
+    (0..size).map(move |_| rng.poisson_one())
+}
+
+// small, fast generator, good enough for bootstrap multipliers
+// (see https://prng.di.unimi.it/splitmix64.c)
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    fn uniform(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Knuth's multiplication method, cheap for a mean of 1
+    fn poisson_one(&mut self) -> u32 {
+        let limit = (-1.0f64).exp();
+        let mut k = 0;
+        let mut p = self.uniform();
+        while p > limit {
+            k += 1;
+            p *= self.uniform();
+        }
+        k
+    }
+}
+
+impl StorageElement for Bootstrap {
+    fn zero(&self) -> Self {
+        Self::new(self.len())
+    }
+
+    fn fill(&mut self, _weight: f32) -> Result<(), StorageError> {
+        Err(StorageError::SeedRequired)
+    }
+
+    fn fill_seeded(&mut self, weight: f32, seed: u64) -> Result<(), StorageError> {
+        self.sumw += weight;
+        let multipliers = multipliers(seed, self.len());
+        for (replica, multiplier) in self.replicas.iter_mut().zip(multipliers) {
+            *replica += multiplier as f32 * weight;
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+        if other.len() != self.len() {
+            return Err(StorageError::WeightsMismatch {
+                expected: self.len(),
+                got: other.len(),
+            });
+        }
+        self.sumw += other.sumw;
+        for (a, b) in self.replicas.iter_mut().zip(&other.replicas) {
+            *a += b;
+        }
+        Ok(())
+    }
+
+    fn scale(&mut self, factor: f64) {
+        self.sumw = (self.sumw as f64 * factor) as f32;
+        for replica in self.replicas.iter_mut() {
+            *replica = (*replica as f64 * factor) as f32;
+        }
+    }
+
+    fn value(&self) -> f64 {
+        self.sumw as f64
+    }
+
+    // sample variance of the replicas
+    fn variance(&self) -> f64 {
+        self.covariance(self).unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bootstrap_multipliers() {
+        let first: Vec<u32> = multipliers(42, 10).collect();
+        assert_eq!(first, multipliers(42, 10).collect::<Vec<u32>>());
+        assert_ne!(first, multipliers(43, 10).collect::<Vec<u32>>());
+
+        // Poisson(1): mean and variance of 1
+        let draws: Vec<f64> = multipliers(7, 100_000).map(|k| k as f64).collect();
+        let mean = draws.iter().sum::<f64>() / draws.len() as f64;
+        let var = draws.iter().map(|k| (k - mean) * (k - mean)).sum::<f64>() / draws.len() as f64;
+        assert!((mean - 1.0).abs() < 0.01);
+        assert!((var - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_bootstrap_covariance() {
+        let (mut a, mut b, mut c) = (
+            Bootstrap::new(200),
+            Bootstrap::new(200),
+            Bootstrap::new(200),
+        );
+        for event in 0..2000u64 {
+            a.fill_seeded(1.0, event).unwrap();
+            // same events with a different weight: fully correlated
+            b.fill_seeded(2.0, event).unwrap();
+            // different events: uncorrelated
+            c.fill_seeded(1.0, event + 1_000_000).unwrap();
+        }
+        assert_eq!(a.value(), 2000.0);
+        // the variance of a sum of n unit weights is n
+        assert!((a.variance() / 2000.0 - 1.0).abs() < 0.25);
+        let cov = covariance_matrix(&[a.clone(), b, c]).unwrap();
+        assert!((cov[0][1] / (cov[0][0] * cov[1][1]).sqrt() - 1.0).abs() < 1e-6);
+        assert!((cov[0][2] / (cov[0][0] * cov[2][2]).sqrt()).abs() < 0.25);
+        assert!(a.fill(1.0).is_err());
+        assert!(a.covariance(&Bootstrap::new(3)).is_err());
+    }
+}
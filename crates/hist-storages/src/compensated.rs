@@ -0,0 +1,150 @@
+use crate::{StorageElement, StorageError};
+
+// Sum with a running compensation term (Kahan-Babuska-Neumaier summation),
+// the rounding error of each addition is collected separately so long sums of
+// `f32` weights keep (close to) full precision
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CompensatedSum {
+    pub sum: f32,
+    pub compensation: f32,
+}
+
+impl CompensatedSum {
+    // `value` split into its `f32` rounding and the remainder
+    pub fn new(value: f64) -> Self {
+        let sum = value as f32;
+        Self {
+            sum,
+            compensation: (value - sum as f64) as f32,
+        }
+    }
+
+    pub fn add(&mut self, value: f32) {
+        let sum = self.sum + value;
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - sum) + value;
+        } else {
+            self.compensation += (value - sum) + self.sum;
+        }
+        self.sum = sum;
+    }
+
+    // adds another sum, keeping both compensation terms
+    pub fn merge(&mut self, other: &CompensatedSum) {
+        self.add(other.sum);
+        self.compensation += other.compensation;
+    }
+
+    pub fn scale(&mut self, factor: f64) {
+        self.sum = (self.sum as f64 * factor) as f32;
+        self.compensation = (self.compensation as f64 * factor) as f32;
+    }
+
+    pub fn value(&self) -> f64 {
+        self.sum as f64 + self.compensation as f64
+    }
+}
+
+// Sum of weights and sum of squared weights (like `Storage::Weight`) with compensated summation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CompensatedWeight {
+    pub sum_of_weights: CompensatedSum,
+    pub sum_of_weights_squared: CompensatedSum,
+}
+
+impl StorageElement for CompensatedWeight {
+    fn zero(&self) -> Self {
+        CompensatedWeight::default()
+    }
+
+    fn fill(&mut self, weight: f32) -> Result<(), StorageError> {
+        self.sum_of_weights.add(weight);
+        self.sum_of_weights_squared.add(weight * weight);
+        Ok(())
+    }
+
+    fn set(&mut self, value: f64, variance: f64) -> Result<(), StorageError> {
+        self.sum_of_weights = CompensatedSum::new(value);
+        self.sum_of_weights_squared = CompensatedSum::new(variance);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+        self.sum_of_weights.merge(&other.sum_of_weights);
+        self.sum_of_weights_squared
+            .merge(&other.sum_of_weights_squared);
+        Ok(())
+    }
+
+    fn scale(&mut self, factor: f64) {
+        self.sum_of_weights.scale(factor);
+        self.sum_of_weights_squared.scale(factor * factor);
+    }
+
+    fn value(&self) -> f64 {
+        self.sum_of_weights.value()
+    }
+
+    fn variance(&self) -> f64 {
+        self.sum_of_weights_squared.value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic weights in [0.5, 1.5)
+    fn weights(n: usize) -> Vec<f32> {
+        let mut state: u32 = 12345;
+        (0..n)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                0.5 + (state >> 8) as f32 / (1 << 24) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compensated_sum() {
+        let weights = weights(1_000_000);
+        let (mut sumw, mut sumw2) = (0.0f64, 0.0f64);
+        let (mut naive, mut bin) = (0.0f32, CompensatedWeight::default());
+        for &weight in &weights {
+            sumw += weight as f64;
+            sumw2 += (weight * weight) as f64;
+            naive += weight;
+            bin.fill(weight).unwrap();
+        }
+        // the plain f32 sum drifts, the compensated one matches the f64 reference
+        assert!((naive as f64 - sumw).abs() / sumw > 1e-5);
+        assert!((bin.value() - sumw).abs() / sumw < 1e-7);
+        assert!((bin.variance() - sumw2).abs() / sumw2 < 1e-7);
+
+        // merging partial sums keeps the compensation
+        let (mut left, mut right) = (CompensatedWeight::default(), CompensatedWeight::default());
+        for &weight in &weights[..300_000] {
+            left.fill(weight).unwrap();
+        }
+        for &weight in &weights[300_000..] {
+            right.fill(weight).unwrap();
+        }
+        left.merge(&right).unwrap();
+        assert!((left.value() - sumw).abs() / sumw < 1e-7);
+        assert!((left.variance() - sumw2).abs() / sumw2 < 1e-7);
+    }
+
+    #[test]
+    fn test_compensated_sum_cancellation() {
+        // small values next to a large one are lost in a plain sum
+        let mut sum = CompensatedSum::default();
+        for value in [1.0, 1e8, 1.0, -1e8] {
+            sum.add(value);
+        }
+        assert_eq!(sum.value(), 2.0);
+        assert_eq!(1.0f32 + 1e8 + 1.0 - 1e8, 0.0);
+        // an f64 value keeps the precision lost in its f32 rounding
+        let value = 1.0 + 1e-9;
+        assert!((CompensatedSum::new(value).value() - value).abs() < 1e-15);
+    }
+}
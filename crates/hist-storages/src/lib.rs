@@ -1,10 +1,241 @@
+use compensated::CompensatedWeight;
+use mean::{Mean, WeightedMean};
+use std::fmt::Debug;
 use std::ops::{Add, AddAssign};
+use thiserror::Error;
 
-#[derive(Debug, Clone)]
+pub mod bootstrap;
+pub mod compensated;
+pub mod mean;
+pub mod multiweight;
+pub mod unlimited;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum StorageError {
+    #[error("{0:?} storage needs a sample to fill, use `fill_with_sample`")]
+    SampleRequired(StorageType),
+    #[error("{0} storage does not accept samples, use a Mean or WeightedMean storage")]
+    SampleNotSupported(String),
+    #[error("cannot convert {from:?} storage to {to:?} storage")]
+    InvalidConversion { from: StorageType, to: StorageType },
+    #[error("cannot merge {left:?} storage with {right:?} storage")]
+    IncompatibleStorages {
+        left: StorageType,
+        right: StorageType,
+    },
+    #[error("{0} storage holds a single weight, use a MultiWeight storage")]
+    MultipleWeightsNotSupported(String),
+    #[error("expected {expected} weights, got {got}")]
+    WeightsMismatch { expected: usize, got: usize },
+    #[error("variation {index} out of range for {len} weights")]
+    VariationOutOfRange { index: usize, len: usize },
+    #[error("Bootstrap storage needs an event seed to fill, use `fill_with_seed`")]
+    SeedRequired,
+    #[error("{0} storage does not use event seeds, use a Bootstrap storage")]
+    SeedNotSupported(String),
+    #[error("{0} storage cannot be set to a floating-point value")]
+    SetNotSupported(String),
+}
+
+// Content of a single bin, histogram backends are generic over it:
+// the built-in storages implement it and custom accumulators (e.g. min/max tracking)
+// can be plugged in the same way
+pub trait StorageElement: Debug + Clone {
+    // empty bin of the same kind as `self`
+    fn zero(&self) -> Self;
+
+    // adds an entry with `weight`
+    fn fill(&mut self, weight: f32) -> Result<(), StorageError>;
+
+    // adds a `sample` with `weight`, only profile storages support this
+    fn fill_sample(&mut self, _sample: f64, _weight: f32) -> Result<(), StorageError> {
+        Err(StorageError::SampleNotSupported(
+            std::any::type_name::<Self>().to_string(),
+        ))
+    }
+
+    // adds an entry with one weight per variation, only multi-weight storages support this
+    fn fill_weights(&mut self, _weights: &[f32]) -> Result<(), StorageError> {
+        Err(StorageError::MultipleWeightsNotSupported(
+            std::any::type_name::<Self>().to_string(),
+        ))
+    }
+
+    // adds an entry with `weight` for the event `seed`, only bootstrap storages support this
+    fn fill_seeded(&mut self, _weight: f32, _seed: u64) -> Result<(), StorageError> {
+        Err(StorageError::SeedNotSupported(
+            std::any::type_name::<Self>().to_string(),
+        ))
+    }
+
+    // replaces the contents by `value` with `variance` (e.g. the result of an unfolding),
+    // storages that infer the variance keep only the value,
+    // integer and profile storages do not support this
+    fn set(&mut self, _value: f64, _variance: f64) -> Result<(), StorageError> {
+        Err(StorageError::SetNotSupported(
+            std::any::type_name::<Self>().to_string(),
+        ))
+    }
+
+    // adds the contents of `other`
+    fn merge(&mut self, other: &Self) -> Result<(), StorageError>;
+
+    // multiplies the contents by `factor`
+    fn scale(&mut self, factor: f64);
+
+    // bin value: sum of weights, or the mean of the sample for profile storages
+    fn value(&self) -> f64;
+
+    // variance of `value`, Poisson (variance = value) if only counts are stored
+    fn variance(&self) -> f64;
+
+    // true for integer counters, which truncate fractional weights and scale factors
+    fn is_integer(&self) -> bool {
+        false
+    }
+
+    // true for profile storages, whose value is the mean of the filled samples
+    fn is_profile(&self) -> bool {
+        false
+    }
+
+    // (effective) number of entries: value^2 / variance for weighted fills
+    fn count(&self) -> f64 {
+        let variance = self.variance();
+        if variance == 0.0 {
+            self.value()
+        } else {
+            self.value() * self.value() / variance
+        }
+    }
+}
+
+macro_rules! impl_counter_storage {
+    ($($ty:ty => $integer:expr),*) => {
+        $(
+            impl StorageElement for $ty {
+                fn zero(&self) -> Self {
+                    0 as $ty
+                }
+
+                fn fill(&mut self, weight: f32) -> Result<(), StorageError> {
+                    *self += weight as $ty;
+                    Ok(())
+                }
+
+                fn set(&mut self, value: f64, _variance: f64) -> Result<(), StorageError> {
+                    if $integer {
+                        return Err(StorageError::SetNotSupported(stringify!($ty).to_string()));
+                    }
+                    *self = value as $ty;
+                    Ok(())
+                }
+
+                fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+                    *self += *other;
+                    Ok(())
+                }
+
+                fn scale(&mut self, factor: f64) {
+                    *self = (*self as f64 * factor) as $ty;
+                }
+
+                fn value(&self) -> f64 {
+                    *self as f64
+                }
+
+                fn variance(&self) -> f64 {
+                    *self as f64
+                }
+
+                fn is_integer(&self) -> bool {
+                    $integer
+                }
+            }
+        )*
+    };
+}
+
+impl_counter_storage!(f32 => false, f64 => false, i32 => true, i64 => true);
+
+// sum of weights and sum of squared weights (see `Storage::Weight`)
+impl StorageElement for (f32, f32) {
+    fn zero(&self) -> Self {
+        (0.0, 0.0)
+    }
+
+    fn fill(&mut self, weight: f32) -> Result<(), StorageError> {
+        self.0 += weight;
+        self.1 += weight * weight;
+        Ok(())
+    }
+
+    fn set(&mut self, value: f64, variance: f64) -> Result<(), StorageError> {
+        *self = (value as f32, variance as f32);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+        self.0 += other.0;
+        self.1 += other.1;
+        Ok(())
+    }
+
+    fn scale(&mut self, factor: f64) {
+        self.0 = (self.0 as f64 * factor) as f32;
+        self.1 = (self.1 as f64 * factor * factor) as f32;
+    }
+
+    fn value(&self) -> f64 {
+        self.0 as f64
+    }
+
+    fn variance(&self) -> f64 {
+        self.1 as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StorageType {
     Double,
     Int,
     Weight,
+    Double64,
+    Int64,
+    CompensatedWeight,
+    Mean,
+    WeightedMean,
+}
+
+impl StorageType {
+    // empty bin
+    pub fn zero(&self) -> Storage {
+        match self {
+            StorageType::Mean => Storage::Mean(Mean::default()),
+            StorageType::WeightedMean => Storage::WeightedMean(WeightedMean::default()),
+            _ => self.weighted(0.0).unwrap(),
+        }
+    }
+
+    // contribution of a single entry with `weight`
+    // mean storages need a sample (see `Storage::fill_sample`)
+    pub fn weighted(&self, weight: f32) -> Result<Storage, StorageError> {
+        match self {
+            StorageType::Double => Ok(Storage::Double(weight)),
+            StorageType::Int => Ok(Storage::Int(weight as i32)),
+            StorageType::Weight => Ok(Storage::Weight((weight, weight * weight))),
+            StorageType::Double64 => Ok(Storage::Double64(weight as f64)),
+            StorageType::Int64 => Ok(Storage::Int64(weight as i64)),
+            StorageType::CompensatedWeight => {
+                let mut bin = CompensatedWeight::default();
+                bin.fill(weight)?;
+                Ok(Storage::CompensatedWeight(bin))
+            }
+            StorageType::Mean | StorageType::WeightedMean => {
+                Err(StorageError::SampleRequired(*self))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -12,28 +243,353 @@ pub enum Storage {
     Double(f32),
     Int(i32),
     Weight((f32, f32)),
+    Double64(f64),
+    Int64(i64),
+    CompensatedWeight(CompensatedWeight),
+    Mean(Mean),
+    WeightedMean(WeightedMean),
 }
 
+impl Storage {
+    pub fn storage_type(&self) -> StorageType {
+        match self {
+            Storage::Double(_) => StorageType::Double,
+            Storage::Int(_) => StorageType::Int,
+            Storage::Weight(_) => StorageType::Weight,
+            Storage::Double64(_) => StorageType::Double64,
+            Storage::Int64(_) => StorageType::Int64,
+            Storage::CompensatedWeight(_) => StorageType::CompensatedWeight,
+            Storage::Mean(_) => StorageType::Mean,
+            Storage::WeightedMean(_) => StorageType::WeightedMean,
+        }
+    }
+
+    // per-bin mean of the sample, `None` for non-mean storages
+    pub fn mean(&self) -> Option<f64> {
+        match self {
+            Storage::Mean(mean) => Some(mean.value()),
+            Storage::WeightedMean(mean) => Some(mean.value()),
+            _ => None,
+        }
+    }
+
+    // per-bin standard error of the mean, `None` for non-mean storages
+    pub fn std_error(&self) -> Option<f64> {
+        match self {
+            Storage::Mean(mean) => Some(mean.std_error()),
+            Storage::WeightedMean(mean) => Some(mean.std_error()),
+            _ => None,
+        }
+    }
+
+    // sum of two bins of the same storage type
+    pub fn checked_add(mut self, other: Storage) -> Result<Storage, StorageError> {
+        self.merge(&other)?;
+        Ok(self)
+    }
+
+    // converts the bin content to another storage type
+    // integer storages round, `Weight` assumes Poisson variances (sumw2 = sumw),
+    // mean storages convert to their sum of weights but can't be created from counts
+    pub fn convert(&self, storage: &StorageType) -> Result<Storage, StorageError> {
+        let sumw = match *self {
+            Storage::Double(val) => val as f64,
+            Storage::Int(val) => val as f64,
+            Storage::Weight((sumw, _)) => sumw as f64,
+            Storage::Double64(val) => val,
+            Storage::Int64(val) => val as f64,
+            Storage::CompensatedWeight(val) => val.value(),
+            Storage::Mean(mean) => mean.count,
+            Storage::WeightedMean(mean) => mean.sum_of_weights,
+        };
+        match (storage, *self) {
+            (StorageType::Weight, Storage::Weight(val)) => Ok(Storage::Weight(val)),
+            (StorageType::Weight, Storage::CompensatedWeight(val)) => {
+                Ok(Storage::Weight((val.value() as f32, val.variance() as f32)))
+            }
+            (StorageType::Weight, Storage::WeightedMean(mean)) => Ok(Storage::Weight((
+                mean.sum_of_weights as f32,
+                mean.sum_of_weights_squared as f32,
+            ))),
+            (StorageType::Weight, _) => Ok(Storage::Weight((sumw as f32, sumw as f32))),
+            (StorageType::Double, _) => Ok(Storage::Double(sumw as f32)),
+            (StorageType::Int, _) => Ok(Storage::Int(sumw.round() as i32)),
+            (StorageType::Double64, _) => Ok(Storage::Double64(sumw)),
+            (StorageType::Int64, _) => Ok(Storage::Int64(sumw.round() as i64)),
+            (StorageType::CompensatedWeight, Storage::CompensatedWeight(val)) => {
+                Ok(Storage::CompensatedWeight(val))
+            }
+            (StorageType::CompensatedWeight, _) => {
+                // assume Poisson variances (as for `Weight`) unless they are stored
+                let sumw2 = match *self {
+                    Storage::Weight((_, sumw2)) => sumw2,
+                    _ => sumw as f32,
+                };
+                let mut val = CompensatedWeight::default();
+                val.sum_of_weights.add(sumw as f32);
+                val.sum_of_weights_squared.add(sumw2);
+                Ok(Storage::CompensatedWeight(val))
+            }
+            (StorageType::Mean, Storage::Mean(mean)) => Ok(Storage::Mean(mean)),
+            (StorageType::WeightedMean, Storage::WeightedMean(mean)) => {
+                Ok(Storage::WeightedMean(mean))
+            }
+            (to, from) => Err(StorageError::InvalidConversion {
+                from: from.storage_type(),
+                to: *to,
+            }),
+        }
+    }
+}
+
+// the storage type is chosen at runtime
+impl StorageElement for Storage {
+    fn zero(&self) -> Self {
+        self.storage_type().zero()
+    }
+
+    fn fill(&mut self, weight: f32) -> Result<(), StorageError> {
+        match self {
+            Storage::Double(val) => val.fill(weight),
+            Storage::Int(val) => val.fill(weight),
+            Storage::Weight(val) => val.fill(weight),
+            Storage::Double64(val) => val.fill(weight),
+            Storage::Int64(val) => val.fill(weight),
+            Storage::CompensatedWeight(val) => val.fill(weight),
+            Storage::Mean(mean) => mean.fill(weight),
+            Storage::WeightedMean(mean) => mean.fill(weight),
+        }
+    }
+
+    fn fill_sample(&mut self, sample: f64, weight: f32) -> Result<(), StorageError> {
+        match self {
+            Storage::Mean(mean) => mean.fill_sample(sample, weight),
+            Storage::WeightedMean(mean) => mean.fill_sample(sample, weight),
+            _ => Err(StorageError::SampleNotSupported(format!(
+                "{:?}",
+                self.storage_type()
+            ))),
+        }
+    }
+
+    fn set(&mut self, value: f64, variance: f64) -> Result<(), StorageError> {
+        match self {
+            Storage::Double(val) => val.set(value, variance),
+            Storage::Weight(val) => val.set(value, variance),
+            Storage::Double64(val) => val.set(value, variance),
+            Storage::CompensatedWeight(val) => val.set(value, variance),
+            _ => Err(StorageError::SetNotSupported(format!(
+                "{:?}",
+                self.storage_type()
+            ))),
+        }
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+        match (self, other) {
+            (Storage::Double(a), Storage::Double(b)) => a.merge(b),
+            (Storage::Int(a), Storage::Int(b)) => a.merge(b),
+            (Storage::Weight(a), Storage::Weight(b)) => a.merge(b),
+            (Storage::Double64(a), Storage::Double64(b)) => a.merge(b),
+            (Storage::Int64(a), Storage::Int64(b)) => a.merge(b),
+            (Storage::CompensatedWeight(a), Storage::CompensatedWeight(b)) => a.merge(b),
+            (Storage::Mean(a), Storage::Mean(b)) => a.merge(b),
+            (Storage::WeightedMean(a), Storage::WeightedMean(b)) => a.merge(b),
+            (left, right) => Err(StorageError::IncompatibleStorages {
+                left: left.storage_type(),
+                right: right.storage_type(),
+            }),
+        }
+    }
+
+    fn scale(&mut self, factor: f64) {
+        match self {
+            Storage::Double(val) => val.scale(factor),
+            Storage::Int(val) => val.scale(factor),
+            Storage::Weight(val) => val.scale(factor),
+            Storage::Double64(val) => val.scale(factor),
+            Storage::Int64(val) => val.scale(factor),
+            Storage::CompensatedWeight(val) => val.scale(factor),
+            Storage::Mean(mean) => mean.scale(factor),
+            Storage::WeightedMean(mean) => mean.scale(factor),
+        }
+    }
+
+    fn value(&self) -> f64 {
+        match self {
+            Storage::Double(val) => val.value(),
+            Storage::Int(val) => val.value(),
+            Storage::Weight(val) => val.value(),
+            Storage::Double64(val) => val.value(),
+            Storage::Int64(val) => val.value(),
+            Storage::CompensatedWeight(val) => val.value(),
+            Storage::Mean(mean) => mean.value(),
+            Storage::WeightedMean(mean) => mean.value(),
+        }
+    }
+
+    fn variance(&self) -> f64 {
+        match self {
+            Storage::Double(val) => val.variance(),
+            Storage::Int(val) => val.variance(),
+            Storage::Weight(val) => val.variance(),
+            Storage::Double64(val) => val.variance(),
+            Storage::Int64(val) => val.variance(),
+            Storage::CompensatedWeight(val) => val.variance(),
+            Storage::Mean(mean) => mean.variance(),
+            Storage::WeightedMean(mean) => mean.variance(),
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(self, Storage::Int(_) | Storage::Int64(_))
+    }
+
+    fn is_profile(&self) -> bool {
+        matches!(self, Storage::Mean(_) | Storage::WeightedMean(_))
+    }
+
+    fn count(&self) -> f64 {
+        match self {
+            Storage::Double(val) => val.count(),
+            Storage::Int(val) => val.count(),
+            Storage::Weight(val) => val.count(),
+            Storage::Double64(val) => val.count(),
+            Storage::Int64(val) => val.count(),
+            Storage::CompensatedWeight(val) => val.count(),
+            Storage::Mean(mean) => mean.count(),
+            Storage::WeightedMean(mean) => mean.count(),
+        }
+    }
+}
+
+// panics for different storage types, see `checked_add`
 impl Add for Storage {
     type Output = Storage;
 
     fn add(self, other: Storage) -> Storage {
-        match (self, other) {
-            (Storage::Double(a), Storage::Double(b)) => Storage::Double(a + b),
-            (Storage::Int(a), Storage::Int(b)) => Storage::Int(a + b),
-            (Storage::Weight((a, b)), Storage::Weight((c, d))) => Storage::Weight((a + c, b + d)),
-            _ => panic!("Cannot add different storage types"),
+        match self.checked_add(other) {
+            Ok(sum) => sum,
+            Err(err) => panic!("{}", err),
         }
     }
 }
 
 impl AddAssign for Storage {
     fn add_assign(&mut self, other: Storage) {
-        *self = match (self.clone(), other) {
-            (Storage::Double(a), Storage::Double(b)) => Storage::Double(a + b),
-            (Storage::Int(a), Storage::Int(b)) => Storage::Int(a + b),
-            (Storage::Weight((a, b)), Storage::Weight((c, d))) => Storage::Weight((a + c, b + d)),
-            _ => panic!("Cannot add different storage types"),
-        }
+        *self = *self + other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_precision() {
+        // f32 stops incrementing at 2^24
+        let start = 16_777_216.0;
+        let mut double = Storage::Double(start as f32);
+        double += StorageType::Double.weighted(1.0).unwrap();
+        assert_eq!(double, Storage::Double(16_777_216.0));
+
+        let mut double64 = Storage::Double64(start);
+        double64 += StorageType::Double64.weighted(1.0).unwrap();
+        assert_eq!(double64, Storage::Double64(16_777_217.0));
+
+        let mut int64 = Storage::Int64(i32::MAX as i64);
+        int64 += StorageType::Int64.weighted(1.0).unwrap();
+        assert_eq!(int64, Storage::Int64(i32::MAX as i64 + 1));
+    }
+
+    #[test]
+    fn test_storage_convert() {
+        let weight = Storage::Weight((2.5, 1.5));
+        assert_eq!(
+            weight.convert(&StorageType::Double64),
+            Ok(Storage::Double64(2.5))
+        );
+        assert_eq!(weight.convert(&StorageType::Int64), Ok(Storage::Int64(3)));
+        assert_eq!(weight.convert(&StorageType::Weight), Ok(weight));
+        assert_eq!(
+            Storage::Int64(4).convert(&StorageType::Weight),
+            Ok(Storage::Weight((4.0, 4.0)))
+        );
+        assert!(weight.convert(&StorageType::Mean).is_err());
+        let compensated = weight.convert(&StorageType::CompensatedWeight).unwrap();
+        assert_eq!(compensated.value(), 2.5);
+        assert_eq!(compensated.variance(), 1.5);
+        assert_eq!(compensated.convert(&StorageType::Weight), Ok(weight));
+        assert_eq!(StorageType::Int64.zero(), Storage::Int64(0));
+    }
+
+    #[test]
+    fn test_storage_mean() {
+        let mut bin = StorageType::Mean.zero();
+        assert!(StorageType::Mean.weighted(1.0).is_err());
+        bin.fill_sample(1.0, 1.0).unwrap();
+        bin.fill_sample(3.0, 1.0).unwrap();
+        assert_eq!(bin.mean(), Some(2.0));
+        assert_eq!(bin.std_error(), Some(1.0));
+
+        let mut other = StorageType::Mean.zero();
+        other.fill_sample(5.0, 2.0).unwrap();
+        bin += other;
+        assert_eq!(bin.mean(), Some(3.5));
+        assert_eq!(
+            bin.checked_add(Storage::Double(1.0)),
+            Err(StorageError::IncompatibleStorages {
+                left: StorageType::Mean,
+                right: StorageType::Double
+            })
+        );
+
+        let mut double = StorageType::Double.zero();
+        assert!(double.fill_sample(1.0, 1.0).is_err());
+        assert_eq!(double.mean(), None);
+    }
+
+    #[test]
+    fn test_storage_element() {
+        let mut weight = (0.0f32, 0.0f32);
+        weight.fill(2.0).unwrap();
+        weight.fill(1.0).unwrap();
+        weight.scale(2.0);
+        assert_eq!(weight, (6.0, 20.0));
+        assert_eq!(weight.variance(), 20.0);
+
+        let mut int = 3i64;
+        int.merge(&4).unwrap();
+        assert_eq!(int.value(), 7.0);
+        assert_eq!(int.variance(), 7.0);
+        assert!(int.is_integer() && !1.0f32.is_integer());
+        assert!(StorageType::Mean.zero().is_profile() && !int.is_profile());
+        assert!(int.set(1.5, 1.0).is_err());
+        let mut double = 3.0f64;
+        double.set(0.25, 1.0).unwrap();
+        assert_eq!(double, 0.25);
+        assert!(int.fill_sample(1.0, 1.0).is_err());
+
+        // the dynamic storage dispatches to the same implementations
+        let mut bin = StorageType::Weight.zero();
+        bin.fill(2.0).unwrap();
+        assert_eq!(bin.variance(), 4.0);
+        let mut set = StorageType::Weight.zero();
+        set.set(0.5, 0.125).unwrap();
+        assert_eq!(set, Storage::Weight((0.5, 0.125)));
+        assert!(StorageType::Mean.zero().set(0.5, 0.125).is_err());
+        assert_eq!(
+            bin.merge(&Storage::Double(1.0)),
+            Err(StorageError::IncompatibleStorages {
+                left: StorageType::Weight,
+                right: StorageType::Double
+            })
+        );
+        assert_eq!(bin.zero(), Storage::Weight((0.0, 0.0)));
+        // effective counts: sumw^2 / sumw2
+        bin.fill(2.0).unwrap();
+        assert_eq!(bin.count(), 2.0);
+        assert_eq!(Storage::Double(3.0).count(), 3.0);
+        assert_eq!(StorageType::Weight.zero().count(), 0.0);
     }
 }
@@ -0,0 +1,252 @@
+use crate::{StorageElement, StorageError, StorageType};
+
+// Profile accumulators (like ROOT's TProfile or boost-histogram's `Mean`/`WeightedMean`)
+// mean and variance of a sample are accumulated with Welford's algorithm,
+// merging uses the parallel variant by Chan et al.
+
+// Count, running mean and variance of a sample,
+// weights act as integer-like multiplicities
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Mean {
+    pub count: f64,
+    pub mean: f64,
+    pub sum_of_deltas_squared: f64,
+}
+
+impl Mean {
+    // sample variance
+    pub fn sample_variance(&self) -> f64 {
+        self.sum_of_deltas_squared / (self.count - 1.0)
+    }
+
+    // standard error of the mean
+    pub fn std_error(&self) -> f64 {
+        (self.sample_variance() / self.count).sqrt()
+    }
+}
+
+impl StorageElement for Mean {
+    fn zero(&self) -> Self {
+        Mean::default()
+    }
+
+    fn fill(&mut self, _weight: f32) -> Result<(), StorageError> {
+        Err(StorageError::SampleRequired(StorageType::Mean))
+    }
+
+    fn fill_sample(&mut self, sample: f64, weight: f32) -> Result<(), StorageError> {
+        let weight = weight as f64;
+        self.count += weight;
+        // e.g. a first fill with weight 0, the mean stays undefined
+        if self.count == 0.0 {
+            return Ok(());
+        }
+        let delta = sample - self.mean;
+        self.mean += weight * delta / self.count;
+        self.sum_of_deltas_squared += weight * delta * (sample - self.mean);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Mean) -> Result<(), StorageError> {
+        let count = self.count + other.count;
+        if count == 0.0 {
+            return Ok(());
+        }
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count / count;
+        self.sum_of_deltas_squared +=
+            other.sum_of_deltas_squared + delta * delta * self.count * other.count / count;
+        self.count = count;
+        Ok(())
+    }
+
+    // scales the sample values, the count is unchanged
+    fn scale(&mut self, factor: f64) {
+        self.mean *= factor;
+        self.sum_of_deltas_squared *= factor * factor;
+    }
+
+    fn value(&self) -> f64 {
+        self.mean
+    }
+
+    // variance of the mean
+    fn variance(&self) -> f64 {
+        self.sample_variance() / self.count
+    }
+
+    fn count(&self) -> f64 {
+        self.count
+    }
+
+    fn is_profile(&self) -> bool {
+        true
+    }
+}
+
+// Sum of weights, running weighted mean and variance of a sample
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WeightedMean {
+    pub sum_of_weights: f64,
+    pub sum_of_weights_squared: f64,
+    pub mean: f64,
+    pub sum_of_weighted_deltas_squared: f64,
+}
+
+impl WeightedMean {
+    // effective number of entries: sumw^2 / sumw2
+    pub fn effective_count(&self) -> f64 {
+        self.sum_of_weights * self.sum_of_weights / self.sum_of_weights_squared
+    }
+
+    // unbiased weighted sample variance (reliability weights)
+    pub fn sample_variance(&self) -> f64 {
+        self.sum_of_weighted_deltas_squared
+            / (self.sum_of_weights - self.sum_of_weights_squared / self.sum_of_weights)
+    }
+
+    // standard error of the mean
+    pub fn std_error(&self) -> f64 {
+        (self.sample_variance() / self.effective_count()).sqrt()
+    }
+}
+
+impl StorageElement for WeightedMean {
+    fn zero(&self) -> Self {
+        WeightedMean::default()
+    }
+
+    fn fill(&mut self, _weight: f32) -> Result<(), StorageError> {
+        Err(StorageError::SampleRequired(StorageType::WeightedMean))
+    }
+
+    fn fill_sample(&mut self, sample: f64, weight: f32) -> Result<(), StorageError> {
+        let weight = weight as f64;
+        self.sum_of_weights += weight;
+        self.sum_of_weights_squared += weight * weight;
+        if self.sum_of_weights == 0.0 {
+            return Ok(());
+        }
+        let delta = sample - self.mean;
+        self.mean += weight * delta / self.sum_of_weights;
+        self.sum_of_weighted_deltas_squared += weight * delta * (sample - self.mean);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &WeightedMean) -> Result<(), StorageError> {
+        let sum_of_weights = self.sum_of_weights + other.sum_of_weights;
+        if sum_of_weights == 0.0 {
+            return Ok(());
+        }
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.sum_of_weights / sum_of_weights;
+        self.sum_of_weighted_deltas_squared += other.sum_of_weighted_deltas_squared
+            + delta * delta * self.sum_of_weights * other.sum_of_weights / sum_of_weights;
+        self.sum_of_weights = sum_of_weights;
+        self.sum_of_weights_squared += other.sum_of_weights_squared;
+        Ok(())
+    }
+
+    // scales the sample values, the weights are unchanged
+    fn scale(&mut self, factor: f64) {
+        self.mean *= factor;
+        self.sum_of_weighted_deltas_squared *= factor * factor;
+    }
+
+    fn value(&self) -> f64 {
+        self.mean
+    }
+
+    // variance of the mean
+    fn variance(&self) -> f64 {
+        self.sample_variance() / self.effective_count()
+    }
+
+    fn count(&self) -> f64 {
+        self.effective_count()
+    }
+
+    fn is_profile(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean() {
+        let samples = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let mut mean = Mean::default();
+        for sample in samples {
+            mean.fill_sample(sample, 1.0).unwrap();
+        }
+        assert_eq!(mean.count, 8.0);
+        assert_eq!(mean.value(), 5.0);
+        // sum of squared deviations is 32
+        assert!((mean.sample_variance() - 32.0 / 7.0).abs() < 1e-12);
+        assert!((mean.std_error() - (32.0 / 7.0 / 8.0f64).sqrt()).abs() < 1e-12);
+
+        // merging two halves gives the same result
+        let (mut left, mut right) = (Mean::default(), Mean::default());
+        for sample in &samples[..3] {
+            left.fill_sample(*sample, 1.0).unwrap();
+        }
+        for sample in &samples[3..] {
+            right.fill_sample(*sample, 1.0).unwrap();
+        }
+        left.merge(&right).unwrap();
+        assert_eq!(left.count, 8.0);
+        assert!((left.value() - 5.0).abs() < 1e-12);
+        assert!((left.sample_variance() - mean.sample_variance()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mean_numerical_stability() {
+        // large offset, naive sum of squares would lose all precision
+        let mut mean = Mean::default();
+        for sample in [1e9 + 4.0, 1e9 + 7.0, 1e9 + 13.0, 1e9 + 16.0] {
+            mean.fill_sample(sample, 1.0).unwrap();
+        }
+        assert_eq!(mean.value(), 1e9 + 10.0);
+        assert_eq!(mean.sample_variance(), 30.0);
+    }
+
+    #[test]
+    fn test_weighted_mean() {
+        let mut mean = WeightedMean::default();
+        mean.fill_sample(1.0, 1.0).unwrap();
+        mean.fill_sample(4.0, 2.0).unwrap();
+        assert_eq!(mean.sum_of_weights, 3.0);
+        assert_eq!(mean.sum_of_weights_squared, 5.0);
+        assert_eq!(mean.value(), 3.0);
+        assert_eq!(mean.effective_count(), 9.0 / 5.0);
+        // sum w (x - mean)^2 = 4 + 2 = 6, normalised by 3 - 5 / 3
+        assert!((mean.sample_variance() - 6.0 / (3.0 - 5.0 / 3.0)).abs() < 1e-12);
+
+        let mut other = WeightedMean::default();
+        other.fill_sample(4.0, 2.0).unwrap();
+        let mut merged = WeightedMean::default();
+        merged.fill_sample(1.0, 1.0).unwrap();
+        merged.merge(&other).unwrap();
+        assert_eq!(merged, mean);
+    }
+
+    #[test]
+    fn test_mean_zero_weight() {
+        // a first fill with weight 0 must not leave the mean at NaN
+        let mut mean = Mean::default();
+        mean.fill_sample(3.0, 0.0).unwrap();
+        mean.fill_sample(2.0, 1.0).unwrap();
+        mean.fill_sample(4.0, 1.0).unwrap();
+        assert_eq!(mean.value(), 3.0);
+        assert_eq!(mean.sample_variance(), 2.0);
+
+        let mut weighted = WeightedMean::default();
+        weighted.fill_sample(3.0, 0.0).unwrap();
+        weighted.fill_sample(2.0, 2.0).unwrap();
+        assert_eq!(weighted.value(), 2.0);
+        assert_eq!(weighted.sum_of_weighted_deltas_squared, 0.0);
+    }
+}
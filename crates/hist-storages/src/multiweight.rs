@@ -0,0 +1,146 @@
+use crate::{Storage, StorageElement, StorageError};
+
+// Fixed number of weight sums per bin (e.g. one per systematic variation),
+// so all variations are filled with a single bin lookup:
+// - variation 0 is the nominal weight, it is used for `value` and `variance`
+// - sums of squared weights are only kept if requested (`sumw2` is empty otherwise)
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiWeight {
+    pub sumw: Vec<f32>,
+    pub sumw2: Vec<f32>,
+}
+
+impl MultiWeight {
+    pub fn new(size: usize, variances: bool) -> Self {
+        Self {
+            sumw: vec![0.0; size],
+            sumw2: if variances { vec![0.0; size] } else { vec![] },
+        }
+    }
+
+    // number of variations
+    pub fn len(&self) -> usize {
+        self.sumw.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sumw.is_empty()
+    }
+
+    pub fn has_variances(&self) -> bool {
+        !self.sumw2.is_empty()
+    }
+
+    // adds one entry with a separate weight for every variation
+    pub fn fill_weights(&mut self, weights: &[f32]) -> Result<(), StorageError> {
+        if weights.len() != self.len() {
+            return Err(StorageError::WeightsMismatch {
+                expected: self.len(),
+                got: weights.len(),
+            });
+        }
+        for (sumw, weight) in self.sumw.iter_mut().zip(weights) {
+            *sumw += weight;
+        }
+        for (sumw2, weight) in self.sumw2.iter_mut().zip(weights) {
+            *sumw2 += weight * weight;
+        }
+        Ok(())
+    }
+
+    // content of variation `k` as `Weight` storage (`Double` if no sumw2 is kept)
+    pub fn variation(&self, k: usize) -> Result<Storage, StorageError> {
+        let sumw = *self.sumw.get(k).ok_or(StorageError::VariationOutOfRange {
+            index: k,
+            len: self.len(),
+        })?;
+        Ok(match self.sumw2.get(k) {
+            Some(&sumw2) => Storage::Weight((sumw, sumw2)),
+            None => Storage::Double(sumw),
+        })
+    }
+}
+
+impl StorageElement for MultiWeight {
+    fn zero(&self) -> Self {
+        Self::new(self.len(), self.has_variances())
+    }
+
+    // same weight for all variations
+    fn fill(&mut self, weight: f32) -> Result<(), StorageError> {
+        self.sumw.iter_mut().for_each(|sumw| *sumw += weight);
+        self.sumw2
+            .iter_mut()
+            .for_each(|sumw2| *sumw2 += weight * weight);
+        Ok(())
+    }
+
+    fn fill_weights(&mut self, weights: &[f32]) -> Result<(), StorageError> {
+        MultiWeight::fill_weights(self, weights)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), StorageError> {
+        if other.len() != self.len() || other.has_variances() != self.has_variances() {
+            return Err(StorageError::WeightsMismatch {
+                expected: self.len(),
+                got: other.len(),
+            });
+        }
+        for (a, b) in self.sumw.iter_mut().zip(&other.sumw) {
+            *a += b;
+        }
+        for (a, b) in self.sumw2.iter_mut().zip(&other.sumw2) {
+            *a += b;
+        }
+        Ok(())
+    }
+
+    fn scale(&mut self, factor: f64) {
+        for sumw in self.sumw.iter_mut() {
+            *sumw = (*sumw as f64 * factor) as f32;
+        }
+        for sumw2 in self.sumw2.iter_mut() {
+            *sumw2 = (*sumw2 as f64 * factor * factor) as f32;
+        }
+    }
+
+    fn value(&self) -> f64 {
+        self.sumw.first().map_or(0.0, |&sumw| sumw as f64)
+    }
+
+    fn variance(&self) -> f64 {
+        self.sumw2
+            .first()
+            .map_or_else(|| self.value(), |&sumw2| sumw2 as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiweight() {
+        let mut bin = MultiWeight::new(3, true);
+        bin.fill_weights(&[1.0, 2.0, 0.5]).unwrap();
+        StorageElement::fill(&mut bin, 1.0).unwrap();
+        assert_eq!(bin.sumw, vec![2.0, 3.0, 1.5]);
+        assert_eq!(bin.sumw2, vec![2.0, 5.0, 1.25]);
+        assert_eq!(bin.value(), 2.0);
+        assert_eq!(bin.variance(), 2.0);
+        assert_eq!(bin.variation(1), Ok(Storage::Weight((3.0, 5.0))));
+        assert!(bin.variation(3).is_err());
+        assert!(bin.fill_weights(&[1.0]).is_err());
+
+        let mut other = bin.zero();
+        other.fill_weights(&[1.0, 1.0, 1.0]).unwrap();
+        bin.merge(&other).unwrap();
+        assert_eq!(bin.sumw, vec![3.0, 4.0, 2.5]);
+        assert!(bin.merge(&MultiWeight::new(3, false)).is_err());
+
+        let mut counts = MultiWeight::new(2, false);
+        counts.fill_weights(&[2.0, 4.0]).unwrap();
+        assert_eq!(counts.variation(1), Ok(Storage::Double(4.0)));
+        assert_eq!(counts.variance(), 2.0);
+    }
+}
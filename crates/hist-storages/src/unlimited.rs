@@ -0,0 +1,219 @@
+// Packed counter buffer that starts with `u8` elements and promotes its element
+// width on demand (u8 -> u16 -> u32 -> u64 -> f64), similar to boost-histogram's
+// `Unlimited` storage:
+// - integer fills that overflow the current width promote to the next width that fits
+// - negative or non-integer weights promote to `f64`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unlimited {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+    Double(Vec<f64>),
+}
+
+impl Unlimited {
+    pub fn new(size: usize) -> Self {
+        Unlimited::U8(vec![0; size])
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Unlimited::U8(data) => data.len(),
+            Unlimited::U16(data) => data.len(),
+            Unlimited::U32(data) => data.len(),
+            Unlimited::U64(data) => data.len(),
+            Unlimited::Double(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // size of a single element in bytes
+    pub fn element_size(&self) -> usize {
+        match self {
+            Unlimited::U8(_) => 1,
+            Unlimited::U16(_) => 2,
+            Unlimited::U32(_) => 4,
+            Unlimited::U64(_) | Unlimited::Double(_) => 8,
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> f64 {
+        match self {
+            Unlimited::Double(data) => data[idx],
+            _ => self.get_int(idx) as f64,
+        }
+    }
+
+    // adds `weight` to the element at `idx`
+    pub fn fill(&mut self, idx: usize, weight: f32) {
+        if let Unlimited::Double(data) = self {
+            data[idx] += weight as f64;
+            return;
+        }
+        // `as u64` saturates, weights beyond the u64 range go to doubles as well
+        if weight >= 0.0 && weight.fract() == 0.0 && (weight as f64) < u64::MAX as f64 {
+            if let Some(value) = self.get_int(idx).checked_add(weight as u64) {
+                self.set_int(idx, value);
+                return;
+            }
+        }
+        self.promote(Level::Double);
+        if let Unlimited::Double(data) = self {
+            data[idx] += weight as f64;
+        }
+    }
+
+    // sets the element at `idx`, promoting the buffer if `value` does not fit
+    pub fn set(&mut self, idx: usize, value: f64) {
+        if !matches!(self, Unlimited::Double(_))
+            && value >= 0.0
+            && value.fract() == 0.0
+            && value < u64::MAX as f64
+        {
+            self.set_int(idx, value as u64);
+            return;
+        }
+        self.promote(Level::Double);
+        if let Unlimited::Double(data) = self {
+            data[idx] = value;
+        }
+    }
+
+    fn get_int(&self, idx: usize) -> u64 {
+        match self {
+            Unlimited::U8(data) => data[idx] as u64,
+            Unlimited::U16(data) => data[idx] as u64,
+            Unlimited::U32(data) => data[idx] as u64,
+            Unlimited::U64(data) => data[idx],
+            Unlimited::Double(data) => data[idx] as u64,
+        }
+    }
+
+    // stores an integer, the buffer must not hold doubles yet
+    fn set_int(&mut self, idx: usize, value: u64) {
+        self.promote(Level::of(value));
+        match self {
+            Unlimited::U8(data) => data[idx] = value as u8,
+            Unlimited::U16(data) => data[idx] = value as u16,
+            Unlimited::U32(data) => data[idx] = value as u32,
+            Unlimited::U64(data) => data[idx] = value,
+            Unlimited::Double(data) => data[idx] = value as f64,
+        }
+    }
+
+    fn level(&self) -> Level {
+        match self {
+            Unlimited::U8(_) => Level::U8,
+            Unlimited::U16(_) => Level::U16,
+            Unlimited::U32(_) => Level::U32,
+            Unlimited::U64(_) => Level::U64,
+            Unlimited::Double(_) => Level::Double,
+        }
+    }
+
+    // widens the element type to at least `level`
+    fn promote(&mut self, level: Level) {
+        if level <= self.level() {
+            return;
+        }
+        *self = match level {
+            Level::Double => Unlimited::Double((0..self.len()).map(|i| self.get(i)).collect()),
+            _ => {
+                let values = (0..self.len()).map(|i| self.get_int(i));
+                match level {
+                    Level::U16 => Unlimited::U16(values.map(|v| v as u16).collect()),
+                    Level::U32 => Unlimited::U32(values.map(|v| v as u32).collect()),
+                    _ => Unlimited::U64(values.collect()),
+                }
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    U8,
+    U16,
+    U32,
+    U64,
+    Double,
+}
+
+impl Level {
+    // smallest integer width holding `value`
+    fn of(value: u64) -> Level {
+        if value <= u8::MAX as u64 {
+            Level::U8
+        } else if value <= u16::MAX as u64 {
+            Level::U16
+        } else if value <= u32::MAX as u64 {
+            Level::U32
+        } else {
+            Level::U64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_promotion() {
+        let mut buffer = Unlimited::new(3);
+        assert_eq!(buffer.element_size(), 1);
+
+        for _ in 0..255 {
+            buffer.fill(0, 1.0);
+        }
+        assert_eq!(buffer.element_size(), 1);
+        assert_eq!(buffer.get(0), 255.0);
+
+        // overflow of u8
+        buffer.fill(0, 1.0);
+        assert!(matches!(buffer, Unlimited::U16(_)));
+        assert_eq!(buffer.get(0), 256.0);
+
+        // jump straight to u64
+        buffer.fill(1, 5e9);
+        assert!(matches!(buffer, Unlimited::U64(_)));
+        assert_eq!(buffer.get(1), 5e9);
+        assert_eq!(buffer.get(0), 256.0);
+
+        // first non-integer weight switches to doubles
+        buffer.fill(2, 0.5);
+        assert!(matches!(buffer, Unlimited::Double(_)));
+        assert_eq!(buffer.get(2), 0.5);
+        assert_eq!(buffer.get(1), 5e9);
+        assert_eq!(buffer.get(0), 256.0);
+    }
+
+    #[test]
+    fn test_unlimited_negative_weight() {
+        let mut buffer = Unlimited::new(1);
+        buffer.fill(0, 2.0);
+        buffer.fill(0, -3.0);
+        assert!(matches!(buffer, Unlimited::Double(_)));
+        assert_eq!(buffer.get(0), -1.0);
+
+        let mut buffer = Unlimited::new(2);
+        buffer.set(1, 70_000.0);
+        assert!(matches!(buffer, Unlimited::U32(_)));
+        assert_eq!(buffer.get(1), 70_000.0);
+        // 2^64 (`u64::MAX as f64`) does not fit into a u64 counter
+        buffer.set(0, 2f64.powi(64));
+        assert!(matches!(buffer, Unlimited::Double(_)));
+        assert_eq!(buffer.get(0), 2f64.powi(64));
+        assert_eq!(buffer.get(1), 70_000.0);
+
+        // integer-valued, but too large for a u64 counter
+        let mut buffer = Unlimited::new(1);
+        buffer.fill(0, 1e20);
+        assert!(matches!(buffer, Unlimited::Double(_)));
+        assert_eq!(buffer.get(0), 1e20f32 as f64);
+    }
+}
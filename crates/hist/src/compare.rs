@@ -0,0 +1,387 @@
+// Two-sample tests for 1-D histograms (regular bins only, flow bins are ignored):
+// - chi-square test for unweighted/weighted histograms (N. Gagunashvili, as ROOT's `Chi2Test`)
+// - Kolmogorov-Smirnov test on the cumulative distributions
+// - Anderson-Darling k-sample test for tied (binned) data (Scholz & Stephens, 1987)
+// Histograms count as weighted if any variance differs from its value (e.g. `Weight` storage),
+// weighted histograms use their effective number of entries for KS and AD.
+use crate::hist::{check_equal_axes, Histogram};
+use crate::special::{chi2_sf, kolmogorov_sf};
+use anyhow::Result;
+use hist_storages::StorageElement;
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+use thiserror::Error;
+
+// total number of entries above which `ad_sigma` uses asymptotic sums
+const AD_ASYMPTOTIC_ENTRIES: f64 = 1e6;
+const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+
+#[derive(Error, Debug)]
+pub enum CompareError {
+    #[error("expected 1-D histograms, got {0} axes")]
+    NotOneDimensional(usize),
+    #[error("cannot compare histograms without entries")]
+    EmptyHistogram,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub statistic: f64,
+    // degrees of freedom, only for the chi-square test
+    pub ndf: Option<usize>,
+    pub p_value: f64,
+}
+
+// chi-square test of the hypothesis that both histograms have the same shape
+pub fn chi2_test<A, B>(a: &A, b: &B) -> Result<TestResult>
+where
+    A: Histogram + ?Sized,
+    B: Histogram + ?Sized,
+{
+    let (a, b) = (Sample::new(a, b)?, Sample::new(b, a)?);
+    let (chi2, ndf) = match (a.is_weighted(), b.is_weighted()) {
+        (false, false) => chi2_unweighted(&a, &b),
+        (false, true) => chi2_mixed(&a, &b),
+        (true, false) => chi2_mixed(&b, &a),
+        (true, true) => chi2_weighted(&a, &b),
+    };
+    Ok(TestResult {
+        statistic: chi2,
+        ndf: Some(ndf),
+        p_value: chi2_sf(chi2, ndf as f64),
+    })
+}
+
+// Kolmogorov-Smirnov test, the statistic is the largest distance of the cumulative distributions
+pub fn ks_test<A, B>(a: &A, b: &B) -> Result<TestResult>
+where
+    A: Histogram + ?Sized,
+    B: Histogram + ?Sized,
+{
+    let (a, b) = (Sample::new(a, b)?, Sample::new(b, a)?);
+    let (sum_a, sum_b) = (a.sum(), b.sum());
+    let (mut cdf_a, mut cdf_b, mut distance) = (0.0, 0.0, 0.0f64);
+    for (value_a, value_b) in a.values.iter().zip(b.values.iter()) {
+        cdf_a += value_a / sum_a;
+        cdf_b += value_b / sum_b;
+        distance = distance.max((cdf_a - cdf_b).abs());
+    }
+    let (n_a, n_b) = (a.entries(), b.entries());
+    let z = distance * (n_a * n_b / (n_a + n_b)).sqrt();
+    Ok(TestResult {
+        statistic: distance,
+        ndf: None,
+        p_value: kolmogorov_sf(z),
+    })
+}
+
+// Anderson-Darling test, the statistic is A2akN for tied data (midranks),
+// the p-value is interpolated from the standardized statistic
+pub fn ad_test<A, B>(a: &A, b: &B) -> Result<TestResult>
+where
+    A: Histogram + ?Sized,
+    B: Histogram + ?Sized,
+{
+    let samples = [Sample::new(a, b)?, Sample::new(b, a)?];
+    // bin contents in units of (effective) entries
+    let counts: Vec<Vec<f64>> = samples
+        .iter()
+        .map(|sample| {
+            let scale = sample.entries() / sample.sum();
+            sample.values.iter().map(|value| value * scale).collect()
+        })
+        .collect();
+    let sizes: Vec<f64> = counts.iter().map(|c| c.iter().sum()).collect();
+    let n: f64 = sizes.iter().sum();
+
+    let mut statistic = 0.0;
+    for (sample, size) in counts.iter().zip(sizes.iter()) {
+        let (mut below, mut below_all, mut sum) = (0.0, 0.0, 0.0);
+        for (j, &count) in sample.iter().enumerate() {
+            let tied: f64 = counts.iter().map(|c| c[j]).sum();
+            if tied > 0.0 {
+                let midrank = below + 0.5 * count;
+                let midrank_all = below_all + 0.5 * tied;
+                let numerator = (n * midrank - size * midrank_all).powi(2);
+                let denominator = midrank_all * (n - midrank_all) - 0.25 * n * tied;
+                // vanishes if all entries are tied in this bin (the numerator too)
+                if denominator > 0.0 {
+                    sum += tied * numerator / denominator;
+                }
+            }
+            below += count;
+            below_all += tied;
+        }
+        statistic += sum / size;
+    }
+    statistic *= (n - 1.0) / (n * n);
+
+    let t = (statistic - 1.0) / ad_sigma(&sizes);
+    Ok(TestResult {
+        statistic,
+        ndf: None,
+        p_value: ad_p_value(t),
+    })
+}
+
+// bin-by-bin pulls (a - b) / sqrt(var_a + var_b) as a histogram like `a` (flow bins included),
+// each bin is filled once with the pull as weight, bins without variance stay empty
+pub fn pulls<A, B>(a: &A, b: &B) -> Result<A>
+where
+    A: Histogram,
+    B: Histogram + ?Sized,
+{
+    check_equal_axes(a.get_axes(), b.get_axes())?;
+    let mut pulls = a.empty_like(a.get_axes().clone());
+    // bins stored in either histogram, the others have no variance
+    let stored: BTreeSet<usize> = a
+        .iter_bins()
+        .map(|(idx, _)| idx)
+        .chain(b.iter_bins().map(|(idx, _)| idx))
+        .collect();
+    for idx in stored {
+        let (bin_a, bin_b) = (a.get_bin(idx), b.get_bin(idx));
+        let variance = bin_a.variance() + bin_b.variance();
+        if variance <= 0.0 {
+            continue;
+        }
+        let mut pull = bin_a.zero();
+        pull.fill(((bin_a.value() - bin_b.value()) / variance.sqrt()) as f32)?;
+        pulls.update_bin(idx, &mut |bin| *bin = pull.clone());
+    }
+    Ok(pulls)
+}
+
+// regular bins of a 1-D histogram
+struct Sample {
+    values: Vec<f64>,
+    variances: Vec<f64>,
+}
+
+impl Sample {
+    // checks that `hist` is 1-D with the same axis as `other`
+    fn new<H, O>(hist: &H, other: &O) -> Result<Self>
+    where
+        H: Histogram + ?Sized,
+        O: Histogram + ?Sized,
+    {
+        let naxes = hist.get_axes().len();
+        if naxes != 1 {
+            return Err(CompareError::NotOneDimensional(naxes).into());
+        }
+        check_equal_axes(hist.get_axes(), other.get_axes())?;
+        let sample = Self {
+            values: hist.values(false),
+            variances: hist.variances(false),
+        };
+        if sample.sum() <= 0.0 {
+            return Err(CompareError::EmptyHistogram.into());
+        }
+        Ok(sample)
+    }
+
+    fn is_weighted(&self) -> bool {
+        self.values
+            .iter()
+            .zip(self.variances.iter())
+            .any(|(value, variance)| value != variance)
+    }
+
+    fn sum(&self) -> f64 {
+        self.values.iter().sum()
+    }
+
+    // effective number of entries: sumw^2 / sumw2
+    fn entries(&self) -> f64 {
+        self.sum().powi(2) / self.variances.iter().sum::<f64>()
+    }
+}
+
+// unweighted vs unweighted: (M n_i - N m_i)^2 / (N M (n_i + m_i))
+fn chi2_unweighted(a: &Sample, b: &Sample) -> (f64, usize) {
+    let (n, m) = (a.sum(), b.sum());
+    let (mut chi2, mut nbins) = (0.0, 0);
+    for (&n_i, &m_i) in a.values.iter().zip(b.values.iter()) {
+        if n_i + m_i > 0.0 {
+            chi2 += (m * n_i - n * m_i).powi(2) / (n_i + m_i);
+            nbins += 1;
+        }
+    }
+    (chi2 / (n * m), nbins.max(1) - 1)
+}
+
+// unweighted `a` vs weighted `b`, bins of `b` without entries use the variance
+// of a single entry with the mean weight
+fn chi2_mixed(a: &Sample, b: &Sample) -> (f64, usize) {
+    let (n, w) = (a.sum(), b.sum());
+    let mean_weight = b.variances.iter().sum::<f64>() / w;
+    let (mut chi2, mut nbins) = (0.0, 0);
+    for ((&n_i, &w_i), &variance) in a.values.iter().zip(b.values.iter()).zip(b.variances.iter()) {
+        if n_i == 0.0 && w_i == 0.0 {
+            continue;
+        }
+        let s2 = if variance > 0.0 {
+            variance
+        } else {
+            mean_weight * mean_weight
+        };
+        let x = w * w_i - n * s2;
+        // estimated probability of the bin
+        let p = (x + (x * x + 4.0 * w * w * s2 * n_i).sqrt()) / (2.0 * w * w);
+        chi2 += (n_i - n * p).powi(2) / (n * p) + (w_i - w * p).powi(2) / s2;
+        nbins += 1;
+    }
+    (chi2, nbins.max(1) - 1)
+}
+
+// weighted vs weighted: (W1 w2_i - W2 w1_i)^2 / (W1^2 s2_i^2 + W2^2 s1_i^2)
+fn chi2_weighted(a: &Sample, b: &Sample) -> (f64, usize) {
+    let (w1, w2) = (a.sum(), b.sum());
+    let (mut chi2, mut nbins) = (0.0, 0);
+    for i in 0..a.values.len() {
+        let variance = w1 * w1 * b.variances[i] + w2 * w2 * a.variances[i];
+        if variance > 0.0 {
+            chi2 += (w1 * b.values[i] - w2 * a.values[i]).powi(2) / variance;
+            nbins += 1;
+        }
+    }
+    (chi2, nbins.max(1) - 1)
+}
+
+// standard deviation of A2akN under the null hypothesis (Scholz & Stephens, eq. 4)
+fn ad_sigma(sizes: &[f64]) -> f64 {
+    let k = sizes.len() as f64;
+    let n = sizes.iter().sum::<f64>().round().max(4.0);
+    let big_h: f64 = sizes.iter().map(|size| 1.0 / size).sum();
+    // h = sum_{i < N} 1 / i and g = sum_{i < j < N} 1 / ((N - i) j), their asymptotic forms
+    // are used for large samples (g converges slowly, about ln(N) / N)
+    let (h, g) = if n > AD_ASYMPTOTIC_ENTRIES {
+        ((n - 1.0).ln() + EULER_GAMMA, PI * PI / 6.0)
+    } else {
+        let h: f64 = (1..n as usize).map(|i| 1.0 / i as f64).sum();
+        let mut g = 0.0;
+        let mut tail = h;
+        for i in 1..(n as usize - 1) {
+            tail -= 1.0 / i as f64;
+            g += tail / (n - i as f64);
+        }
+        (h, g)
+    };
+    let a = (4.0 * g - 6.0) * (k - 1.0) + (10.0 - 6.0 * g) * big_h;
+    let b = (2.0 * g - 4.0) * k * k + 8.0 * h * k + (2.0 * g - 14.0 * h - 4.0) * big_h - 8.0 * h
+        + 4.0 * g
+        - 6.0;
+    let c = (6.0 * h + 2.0 * g - 2.0) * k * k
+        + (4.0 * h - 4.0 * g + 6.0) * k
+        + (2.0 * h - 6.0) * big_h
+        + 4.0 * h;
+    let d = (2.0 * h + 6.0) * k * k - 4.0 * h * k;
+    let variance = (a * n.powi(3) + b * n * n + c * n + d) / ((n - 1.0) * (n - 2.0) * (n - 3.0));
+    variance.sqrt()
+}
+
+// p-value of the standardized statistic for two samples: quadratic fit of the
+// log-odds of the tabulated significance levels (Scholz & Stephens, table 1, m = 1)
+fn ad_p_value(t: f64) -> f64 {
+    const LEVELS: [f64; 5] = [0.25, 0.1, 0.05, 0.025, 0.01];
+    const CRITICAL: [f64; 5] = [0.326, 1.225, 1.960, 2.719, 3.752];
+    // least squares fit of log(p / (1 - p)) = c0 + c1 t + c2 t^2
+    let mut normal = [[0.0; 3]; 3];
+    let mut rhs = [0.0; 3];
+    for (&level, &critical) in LEVELS.iter().zip(CRITICAL.iter()) {
+        let powers = [1.0, critical, critical * critical];
+        let log_odds = (level / (1.0 - level)).ln();
+        for i in 0..3 {
+            for j in 0..3 {
+                normal[i][j] += powers[i] * powers[j];
+            }
+            rhs[i] += powers[i] * log_odds;
+        }
+    }
+    let c = solve3(normal, rhs);
+    // the fit is extrapolated linearly beyond the table to keep it monotonic
+    let last = CRITICAL[CRITICAL.len() - 1];
+    let clamped = t.min(last);
+    let log_odds = c[0]
+        + c[1] * clamped
+        + c[2] * clamped * clamped
+        + (c[1] + 2.0 * c[2] * last) * (t - last).max(0.0);
+    1.0 / (1.0 + (-log_odds).exp())
+}
+
+// solves a 3x3 linear system (Cramer's rule)
+fn solve3(m: [[f64; 3]; 3], rhs: [f64; 3]) -> [f64; 3] {
+    let det = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let total = det(&m);
+    let mut solution = [0.0; 3];
+    for (col, value) in solution.iter_mut().enumerate() {
+        let mut replaced = m;
+        for (row, &r) in rhs.iter().enumerate() {
+            replaced[row][col] = r;
+        }
+        *value = det(&replaced) / total;
+    }
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(values: &[f64], weight: f64) -> Sample {
+        Sample {
+            values: values.iter().map(|v| v * weight).collect(),
+            variances: values.iter().map(|v| v * weight * weight).collect(),
+        }
+    }
+
+    #[test]
+    fn test_chi2() {
+        // sum (n_i - m_i)^2 / (n_i + m_i) for equal totals
+        let expected = 4.0 / 22.0 + 4.0 / 38.0;
+        let (a, b) = (
+            sample(&[10.0, 20.0, 30.0], 1.0),
+            sample(&[12.0, 18.0, 30.0], 1.0),
+        );
+        let (chi2, ndf) = chi2_unweighted(&a, &b);
+        assert!((chi2 - expected).abs() < 1e-12);
+        assert_eq!(ndf, 2);
+
+        // a constant weight does not change the weighted test
+        let (a2, b2) = (
+            sample(&[10.0, 20.0, 30.0], 2.0),
+            sample(&[12.0, 18.0, 30.0], 2.0),
+        );
+        assert!(a2.is_weighted());
+        assert_eq!(a2.entries(), 60.0);
+        let (chi2, ndf) = chi2_weighted(&a2, &b2);
+        assert!((chi2 - expected).abs() < 1e-12);
+        assert_eq!(ndf, 2);
+
+        let (chi2, ndf) = chi2_mixed(&a, &b2);
+        assert_eq!(ndf, 2);
+        assert!(chi2 > 0.0 && chi2 < 2.0 * expected);
+        let (identical, _) = chi2_mixed(&a, &a2);
+        assert!(identical.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_ad_p_value() {
+        // reproduces the tabulated critical values
+        assert!((ad_p_value(1.960) - 0.05).abs() < 0.005);
+        assert!((ad_p_value(3.752) - 0.01).abs() < 0.002);
+        assert!(ad_p_value(-1.0) > 0.5);
+        assert!(ad_p_value(20.0) < ad_p_value(10.0));
+        // variance of A2 is close to its asymptotic value for large samples
+        let sigma = ad_sigma(&[500.0, 500.0]);
+        assert!((sigma * sigma - 2.0 * (PI.powi(2) - 9.0) / 3.0).abs() < 0.05);
+        // the asymptotic sums continue the exact ones
+        let (below, above) = (ad_sigma(&[5e5, 5e5]), ad_sigma(&[5e5, 5e5 + 1.0]));
+        assert!((below - above).abs() < 1e-4);
+        assert!(ad_sigma(&[1e12, 1e12]).is_finite());
+    }
+}
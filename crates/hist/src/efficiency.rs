@@ -0,0 +1,177 @@
+// Efficiencies (pass / total) with binomial confidence intervals (like ROOT's `TEfficiency`)
+//
+// For weighted histograms the number of entries is replaced by the effective number of
+// entries of the total histogram, n = sumw^2 / sumw2, with k = efficiency * n passing entries.
+use crate::hist::{check_equal_axes, Histogram};
+use crate::special::{beta_inc_inv, normal_quantile};
+use anyhow::Result;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EfficiencyError {
+    #[error("passed ({passed}) exceeds total ({total}) in bin {bin}")]
+    PassedExceedsTotal { bin: usize, passed: f64, total: f64 },
+    #[error("confidence level {0} outside of (0, 1)")]
+    InvalidConfidence(f64),
+    #[error("prior parameters alpha ({alpha}) and beta ({beta}) must be positive")]
+    InvalidPrior { alpha: f64, beta: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntervalMethod {
+    // exact frequentist interval from beta quantiles (conservative)
+    ClopperPearson,
+    // score interval of the normal approximation
+    Wilson,
+    // central interval of the Beta(k + alpha, n - k + beta) posterior,
+    // `alpha = beta = 1` is a uniform prior
+    Bayesian { alpha: f64, beta: f64 },
+}
+
+// Per-bin efficiency of a `passed` and a `total` histogram with equal axes
+// bins are flat and row-major as in `Histogram::values`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Efficiency {
+    pub shape: Vec<usize>,
+    pub passed: Vec<f64>,
+    pub total: Vec<f64>,
+    // (effective) number of entries of the total histogram
+    pub entries: Vec<f64>,
+}
+
+impl Efficiency {
+    pub fn new<P, T>(passed: &P, total: &T, flow: bool) -> Result<Self>
+    where
+        P: Histogram + ?Sized,
+        T: Histogram + ?Sized,
+    {
+        check_equal_axes(passed.get_axes(), total.get_axes())?;
+        let efficiency = Self {
+            shape: passed.shape(flow),
+            passed: passed.values(flow),
+            total: total.values(flow),
+            entries: total.counts(flow),
+        };
+        for (bin, (&passed, &total)) in efficiency
+            .passed
+            .iter()
+            .zip(efficiency.total.iter())
+            .enumerate()
+        {
+            if passed > total {
+                return Err(EfficiencyError::PassedExceedsTotal { bin, passed, total }.into());
+            }
+        }
+        Ok(efficiency)
+    }
+
+    // passed / total, NaN for empty bins
+    pub fn values(&self) -> Vec<f64> {
+        self.passed
+            .iter()
+            .zip(self.total.iter())
+            .map(|(&passed, &total)| {
+                if total == 0.0 {
+                    f64::NAN
+                } else {
+                    passed / total
+                }
+            })
+            .collect()
+    }
+
+    // (lower, upper) bounds of the efficiency at `confidence` level (e.g. 0.6827 for 1 sigma)
+    pub fn intervals(&self, method: IntervalMethod, confidence: f64) -> Result<Vec<(f64, f64)>> {
+        if !(confidence > 0.0 && confidence < 1.0) {
+            return Err(EfficiencyError::InvalidConfidence(confidence).into());
+        }
+        if let IntervalMethod::Bayesian { alpha, beta } = method {
+            if !(alpha > 0.0 && beta > 0.0) {
+                return Err(EfficiencyError::InvalidPrior { alpha, beta }.into());
+            }
+        }
+        Ok(self
+            .values()
+            .iter()
+            .zip(self.entries.iter())
+            .map(|(&efficiency, &n)| {
+                if n == 0.0 || efficiency.is_nan() {
+                    return (0.0, 1.0);
+                }
+                let k = efficiency * n;
+                match method {
+                    IntervalMethod::ClopperPearson => clopper_pearson(k, n, confidence),
+                    IntervalMethod::Wilson => wilson(k, n, confidence),
+                    IntervalMethod::Bayesian { alpha, beta } => {
+                        bayesian(k, n, confidence, alpha, beta)
+                    }
+                }
+            })
+            .collect())
+    }
+}
+
+pub fn clopper_pearson(k: f64, n: f64, confidence: f64) -> (f64, f64) {
+    let alpha = 0.5 * (1.0 - confidence);
+    let lower = if k <= 0.0 {
+        0.0
+    } else {
+        beta_inc_inv(k, n - k + 1.0, alpha)
+    };
+    let upper = if k >= n {
+        1.0
+    } else {
+        beta_inc_inv(k + 1.0, n - k, 1.0 - alpha)
+    };
+    (lower, upper)
+}
+
+pub fn wilson(k: f64, n: f64, confidence: f64) -> (f64, f64) {
+    let z = normal_quantile(0.5 * (1.0 + confidence));
+    let z2 = z * z;
+    let center = (k + 0.5 * z2) / (n + z2);
+    let half_width = z / (n + z2) * (k * (n - k) / n + 0.25 * z2).sqrt();
+    (
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    )
+}
+
+// `alpha` and `beta` must be positive (the posterior is improper for k = 0 or k = n otherwise)
+pub fn bayesian(k: f64, n: f64, confidence: f64, alpha: f64, beta: f64) -> (f64, f64) {
+    let tail = 0.5 * (1.0 - confidence);
+    let (a, b) = (k + alpha, n - k + beta);
+    (beta_inc_inv(a, b, tail), beta_inc_inv(a, b, 1.0 - tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_SIGMA: f64 = 0.682_689_492_137_085_9;
+
+    #[test]
+    fn test_efficiency_intervals() {
+        let tail: f64 = 0.5 * (1.0 - ONE_SIGMA);
+
+        // closed forms for k = 0 and k = n
+        let (lower, upper) = clopper_pearson(0.0, 10.0, ONE_SIGMA);
+        assert_eq!(lower, 0.0);
+        assert!((upper - (1.0 - tail.powf(0.1))).abs() < 1e-10);
+        let (lower, upper) = clopper_pearson(10.0, 10.0, ONE_SIGMA);
+        assert!((lower - tail.powf(0.1)).abs() < 1e-10);
+        assert_eq!(upper, 1.0);
+        let (lower, upper) = clopper_pearson(5.0, 10.0, ONE_SIGMA);
+        assert!((lower + upper - 1.0).abs() < 1e-10);
+
+        // z = 1: center (k + 1/2) / (n + 1), half width sqrt(k (n - k) / n + 1/4) / (n + 1)
+        let (lower, upper) = wilson(5.0, 10.0, ONE_SIGMA);
+        assert!((lower - (0.5 - 2.75f64.sqrt() / 11.0)).abs() < 1e-8);
+        assert!((upper - (0.5 + 2.75f64.sqrt() / 11.0)).abs() < 1e-8);
+
+        // uniform prior, k = 0: posterior Beta(1, n + 1) with cdf 1 - (1 - x)^(n + 1)
+        let (lower, upper) = bayesian(0.0, 10.0, ONE_SIGMA, 1.0, 1.0);
+        assert!((lower - (1.0 - (1.0 - tail).powf(1.0 / 11.0))).abs() < 1e-10);
+        assert!((upper - (1.0 - tail.powf(1.0 / 11.0))).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,205 @@
+// Parametric fits of 1-D histograms
+//
+// The model `f(x, parameters)` is evaluated at the bin centers and predicts the bin contents.
+// The cost is minimised with Levenberg-Marquardt on the Gauss-Newton approximation of the
+// Hessian (numerical derivatives of the model):
+// - `Cost::ChiSquare`: sum over bins with a non-zero variance of (value - f)^2 / variance
+// - `Cost::Poisson`: Baker-Cousins likelihood ratio 2 sum(f - value + value ln(value / f))
+// Both costs are chi-square distributed at the minimum and give the goodness of fit.
+use crate::hist::Histogram;
+use crate::linalg::{invert, solve};
+use crate::special::chi2_sf;
+use anyhow::Result;
+use hist_axes::bin::Bin;
+use thiserror::Error;
+
+const MAX_ITERATIONS: usize = 500;
+const MAX_DAMPING: f64 = 1e12;
+// relative decrease of the cost at convergence
+const TOLERANCE: f64 = 1e-10;
+
+#[derive(Error, Debug)]
+pub enum FitError {
+    #[error("expected a 1-D histogram, got {0} axes")]
+    NotOneDimensional(usize),
+    #[error("cannot fit a histogram with a categorical axis")]
+    NotContinuous,
+    #[error("{nbins} usable bins are not enough to fit {nparams} parameters")]
+    TooFewBins { nbins: usize, nparams: usize },
+    #[error("model is not finite (or not positive for a Poisson likelihood) at the start values")]
+    InvalidStart,
+    #[error("singular Hessian at the minimum, parameters are not constrained")]
+    SingularHessian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cost {
+    ChiSquare,
+    Poisson,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitResult {
+    pub parameters: Vec<f64>,
+    pub errors: Vec<f64>,
+    pub covariance: Vec<Vec<f64>>,
+    // value of the cost at the minimum (the chi-square of the goodness of fit)
+    pub chi2: f64,
+    pub ndf: usize,
+    pub p_value: f64,
+    pub iterations: usize,
+    // false if the maximum number of iterations was reached
+    pub converged: bool,
+}
+
+// fits `model` to the regular bins of a 1-D histogram starting from `start`
+pub fn fit<H, F>(hist: &H, model: F, start: &[f64], cost: Cost) -> Result<FitResult>
+where
+    H: Histogram + ?Sized,
+    F: Fn(f64, &[f64]) -> f64,
+{
+    let axes = hist.get_axes();
+    if axes.len() != 1 {
+        return Err(FitError::NotOneDimensional(axes.len()).into());
+    }
+    let bins = axes[0].bins();
+    if bins.iter().any(|bin| !matches!(bin, Bin::Interval(_))) {
+        return Err(FitError::NotContinuous.into());
+    }
+    let (centers, values, variances) =
+        (axes[0].centers(), hist.values(false), hist.variances(false));
+    // bins without variance carry no information for a chi-square
+    let used: Vec<usize> = (0..centers.len())
+        .filter(|&i| cost == Cost::Poisson || variances[i] > 0.0)
+        .collect();
+    if used.len() <= start.len() {
+        return Err(FitError::TooFewBins {
+            nbins: used.len(),
+            nparams: start.len(),
+        }
+        .into());
+    }
+    let problem = Problem {
+        x: used.iter().map(|&i| centers[i]).collect(),
+        y: used.iter().map(|&i| values[i]).collect(),
+        variances: used.iter().map(|&i| variances[i]).collect(),
+        model,
+        cost,
+    };
+
+    let mut parameters = start.to_vec();
+    let mut current = problem.cost(&parameters);
+    if !current.is_finite() {
+        return Err(FitError::InvalidStart.into());
+    }
+    let (mut damping, mut converged, mut iterations) = (1e-3, false, 0);
+    while iterations < MAX_ITERATIONS && !converged {
+        iterations += 1;
+        let (gradient, hessian) = problem.derivatives(&parameters);
+        let mut improved = false;
+        while damping < MAX_DAMPING {
+            let mut damped = hessian.clone();
+            for (i, row) in damped.iter_mut().enumerate() {
+                row[i] += damping * hessian[i][i].max(f64::EPSILON);
+            }
+            let step = solve(damped, gradient.iter().map(|g| -g).collect());
+            let trial: Vec<f64> = match step {
+                Some(step) => parameters
+                    .iter()
+                    .zip(step.iter())
+                    .map(|(p, s)| p + s)
+                    .collect(),
+                None => {
+                    damping *= 10.0;
+                    continue;
+                }
+            };
+            let trial_cost = problem.cost(&trial);
+            if trial_cost.is_finite() && trial_cost <= current {
+                converged = current - trial_cost <= TOLERANCE * (current.abs() + TOLERANCE);
+                parameters = trial;
+                current = trial_cost;
+                damping = (damping / 10.0).max(1e-12);
+                improved = true;
+                break;
+            }
+            damping *= 10.0;
+        }
+        // no step decreases the cost: at the minimum within numerical precision
+        converged |= !improved;
+    }
+
+    let (_, hessian) = problem.derivatives(&parameters);
+    // the costs are -2 ln(L), the covariance is the inverse of half the Hessian
+    let covariance: Vec<Vec<f64>> = invert(&hessian)
+        .ok_or(FitError::SingularHessian)?
+        .iter()
+        .map(|row| row.iter().map(|c| 2.0 * c).collect())
+        .collect();
+    let ndf = problem.x.len() - start.len();
+    Ok(FitResult {
+        errors: (0..start.len()).map(|i| covariance[i][i].sqrt()).collect(),
+        parameters,
+        covariance,
+        chi2: current,
+        ndf,
+        p_value: chi2_sf(current, ndf as f64),
+        iterations,
+        converged,
+    })
+}
+
+struct Problem<F> {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    variances: Vec<f64>,
+    model: F,
+    cost: Cost,
+}
+
+impl<F: Fn(f64, &[f64]) -> f64> Problem<F> {
+    fn cost(&self, parameters: &[f64]) -> f64 {
+        let terms = self.x.iter().zip(self.y.iter()).zip(self.variances.iter());
+        terms
+            .map(|((&x, &y), &variance)| {
+                let f = (self.model)(x, parameters);
+                match self.cost {
+                    Cost::ChiSquare => (y - f).powi(2) / variance,
+                    Cost::Poisson if y > 0.0 && f > 0.0 => 2.0 * (f - y + y * (y / f).ln()),
+                    Cost::Poisson if y > 0.0 => f64::INFINITY,
+                    Cost::Poisson => 2.0 * f,
+                }
+            })
+            .sum()
+    }
+
+    // gradient and Gauss-Newton Hessian of the cost
+    fn derivatives(&self, parameters: &[f64]) -> (Vec<f64>, Vec<Vec<f64>>) {
+        let n = parameters.len();
+        let (mut gradient, mut hessian) = (vec![0.0; n], vec![vec![0.0; n]; n]);
+        for ((&x, &y), &variance) in self.x.iter().zip(self.y.iter()).zip(self.variances.iter()) {
+            let f = (self.model)(x, parameters);
+            let jacobian: Vec<f64> = (0..n)
+                .map(|j| {
+                    let step = f64::EPSILON.cbrt() * parameters[j].abs().max(1.0);
+                    let (mut up, mut down) = (parameters.to_vec(), parameters.to_vec());
+                    up[j] += step;
+                    down[j] -= step;
+                    ((self.model)(x, &up) - (self.model)(x, &down)) / (2.0 * step)
+                })
+                .collect();
+            // d(cost)/df and the (expected) curvature d2(cost)/df2
+            let (slope, curvature) = match self.cost {
+                Cost::ChiSquare => (-2.0 * (y - f) / variance, 2.0 / variance),
+                Cost::Poisson => (2.0 * (1.0 - y / f), 2.0 / f.max(f64::MIN_POSITIVE)),
+            };
+            for j in 0..n {
+                gradient[j] += slope * jacobian[j];
+                for k in 0..n {
+                    hessian[j][k] += curvature * jacobian[j] * jacobian[k];
+                }
+            }
+        }
+        (gradient, hessian)
+    }
+}
@@ -1,3 +1,14 @@
+pub mod compare;
+pub mod efficiency;
+pub mod fit;
+mod linalg;
+pub mod lookup;
+pub mod sampler;
+mod special;
+pub mod unfold;
+
+pub use special::normal_cdf;
+
 // General histogram interface:
 //
 // We want to add multiple different types of histograms,
@@ -10,9 +21,15 @@
 // - NDArrayHist: holds the data as an ndarray
 // - ZarrHist: holds the data (compressed) as a zarr array
 pub mod hist {
+    use crate::sampler::Coordinate;
     use anyhow::Result;
-    use hist_axes::axis::Axis;
-    use hist_storages::Storage;
+    use hist_axes::axis::{Axis, AxisError, AxisMismatch};
+    use hist_axes::bin::{Bin, Value};
+    use hist_axes::category::Category;
+    use hist_axes::integer::Integer;
+    use hist_storages::multiweight::MultiWeight;
+    use hist_storages::{Storage, StorageElement, StorageError};
+    use std::collections::{BTreeMap, HashMap};
     use std::fmt::Debug;
     use thiserror::Error;
 
@@ -20,10 +37,64 @@ pub mod hist {
     pub enum HistError {
         #[error("mismatch in number of values ({nvalues}) and axes ({naxes})")]
         AxesValuesMismatch { nvalues: usize, naxes: usize },
+        #[error("no axis named '{0}'")]
+        UnknownAxis(String),
+        #[error("no value given for axis {0}")]
+        MissingAxisValue(usize),
+        #[error("axis {axis} out of range for histogram with {naxes} axes")]
+        AxisOutOfRange { axis: usize, naxes: usize },
+        #[error("axis {0} is given more than once")]
+        DuplicateAxis(usize),
+        #[error(
+            "axis {axis}{} mismatch: {source}",
+            name.as_ref().map(|name| format!(" ('{}')", name)).unwrap_or_default()
+        )]
+        AxisMismatch {
+            axis: usize,
+            name: Option<String>,
+            source: AxisMismatch,
+        },
+        #[error("axis {0} is not a categorical (Category or Integer) axis")]
+        NotCategorical(usize),
+        #[error("unknown category {0:?}")]
+        UnknownCategory(Value),
+        #[error("category {0:?} is assigned to more than one group")]
+        DuplicateCategory(Value),
+        #[error("quantile {0} outside of [0, 1]")]
+        InvalidQuantile(f64),
+        #[error("cannot normalise a histogram with a sum of zero")]
+        ZeroSum,
+        #[error("index {index} out of range for axis {axis} with {nbins} bins (including flow)")]
+        IndexOutOfRange {
+            axis: usize,
+            index: usize,
+            nbins: usize,
+        },
+    }
+
+    // Position of a quantile inside the bin where the cumulative sum crosses it
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Interpolation {
+        // center of the bin
+        Center,
+        // linear interpolation between the bin edges (entries uniform within the bin)
+        Linear,
+    }
+
+    // Direction of a cumulative sum along an axis
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Direction {
+        // bin i holds the sum of all bins up to and including i
+        Forward,
+        // bin i holds the sum of bin i and all bins after it
+        Backward,
     }
 
     // General histogram interface
     pub trait Histogram {
+        // content of a single bin
+        type Storage: StorageElement;
+
         fn get_axes(&self) -> &Vec<Box<dyn Axis>>;
 
         fn num_bins(&self, flow: bool) -> usize {
@@ -32,7 +103,20 @@ pub mod hist {
             axes.iter().map(|axis| axis.num_bins(flow)).product()
         }
 
-        fn stride_index(&self, indices: &Vec<usize>) -> Result<usize> {
+        // position of the axis with the given name
+        fn axis_index(&self, name: &str) -> Result<usize> {
+            self.get_axes()
+                .iter()
+                .position(|axis| axis.name() == Some(name))
+                .ok_or_else(|| HistError::UnknownAxis(name.to_string()).into())
+        }
+
+        fn axis_by_name(&self, name: &str) -> Result<&dyn Axis> {
+            let idx = self.axis_index(name)?;
+            Ok(self.get_axes()[idx].as_ref())
+        }
+
+        fn stride_index(&self, indices: &[usize]) -> Result<usize> {
             let axes = self.get_axes();
             if indices.len() != axes.len() {
                 return Err(HistError::AxesValuesMismatch {
@@ -43,22 +127,651 @@ pub mod hist {
             }
 
             let mut strided_index = 0;
-            for (axis, idx) in axes.iter().zip(indices.iter()) {
+            for (i, (axis, &idx)) in axes.iter().zip(indices.iter()).enumerate() {
                 let stride = axis.num_bins(true);
+                if idx >= stride {
+                    return Err(HistError::IndexOutOfRange {
+                        axis: i,
+                        index: idx,
+                        nbins: stride,
+                    }
+                    .into());
+                }
                 strided_index = strided_index * stride + idx;
             }
             Ok(strided_index)
         }
 
-        fn get_bin(&self, idx: usize) -> Storage;
+        // inverse of `stride_index`
+        fn unravel_index(&self, idx: usize) -> Vec<usize> {
+            let axes = self.get_axes();
+            let mut indices = vec![0; axes.len()];
+            let mut rest = idx;
+            for (i, axis) in axes.iter().enumerate().rev() {
+                let stride = axis.num_bins(true);
+                indices[i] = rest % stride;
+                rest /= stride;
+            }
+            indices
+        }
+
+        fn get_bin(&self, idx: usize) -> Self::Storage;
+
+        // apply `f` to the bin at the stride index `idx`
+        fn update_bin(&mut self, idx: usize, f: &mut dyn FnMut(&mut Self::Storage));
+
+        // (stride index, content) of all stored bins
+        // dense histograms yield every bin, sparse ones only the filled bins
+        fn iter_bins(&self) -> Box<dyn Iterator<Item = (usize, Self::Storage)> + '_> {
+            Box::new((0..self.num_bins(true)).map(|idx| (idx, self.get_bin(idx))))
+        }
+
+        // number of bins along each axis
+        fn shape(&self, flow: bool) -> Vec<usize> {
+            self.get_axes()
+                .iter()
+                .map(|axis| axis.num_bins(flow))
+                .collect()
+        }
+
+        // bin values (sum of weights, or the mean for profile storages) as a flat,
+        // row-major array of `shape(flow)`, flow bins are placed before (underflow)
+        // and after (overflow) the regular bins of each axis
+        fn values(&self, flow: bool) -> Vec<f64> {
+            bin_view(self, flow, |bin| bin.value())
+        }
+
+        // variances of the bin values: sumw2 for weighted storages,
+        // Poisson variances (= values) if only the sum of weights is stored
+        // and the variance of the mean for profile storages
+        fn variances(&self, flow: bool) -> Vec<f64> {
+            bin_view(self, flow, |bin| bin.variance())
+        }
+
+        // (effective) number of entries per bin: sumw^2 / sumw2 for weighted storages
+        fn counts(&self, flow: bool) -> Vec<f64> {
+            bin_view(self, flow, |bin| bin.count())
+        }
+
+        // new, empty histogram of the same type and storage with different axes
+        fn empty_like(&self, axes: Vec<Box<dyn Axis>>) -> Self
+        where
+            Self: Sized;
 
-        fn fill(&mut self, values: &Vec<usize>, weight: f32) -> Result<()>;
+        fn fill(&mut self, values: &[usize], weight: f32) -> Result<()>;
 
-        fn fill_n(&mut self, values: &Vec<Vec<usize>>, weights: &Vec<f32>) -> Result<()> {
+        fn fill_n(&mut self, values: &[Vec<usize>], weights: &[f32]) -> Result<()> {
             for (values, weight) in values.iter().zip(weights.iter()) {
                 self.fill(values, *weight)?;
             }
             Ok(())
         }
+
+        // fill a profile storage (e.g. `Mean` or `WeightedMean`) with a `sample` value
+        fn fill_with_sample(&mut self, values: &[usize], sample: f64, weight: f32) -> Result<()> {
+            let bin_idx = self.stride_index(values)?;
+            try_update_bin(self, bin_idx, |bin| bin.fill_sample(sample, weight))
+        }
+
+        // fill a multi-weight storage with one weight per variation
+        fn fill_weights(&mut self, values: &[usize], weights: &[f32]) -> Result<()> {
+            let bin_idx = self.stride_index(values)?;
+            try_update_bin(self, bin_idx, |bin| bin.fill_weights(weights))
+        }
+
+        // fill a bootstrap storage, `seed` identifies the event (e.g. a hash of run and event number)
+        fn fill_with_seed(&mut self, values: &[usize], weight: f32, seed: u64) -> Result<()> {
+            let bin_idx = self.stride_index(values)?;
+            try_update_bin(self, bin_idx, |bin| bin.fill_seeded(weight, seed))
+        }
+
+        // fill with (axis name, value) pairs in any order
+        // values outside of a continuous axis go to its flow bins, unknown labels to the overflow bin
+        fn fill_by_name(&mut self, values: &[(&str, Coordinate)], weight: f32) -> Result<()> {
+            let axes = self.get_axes();
+            if values.len() != axes.len() {
+                return Err(HistError::AxesValuesMismatch {
+                    nvalues: values.len(),
+                    naxes: axes.len(),
+                }
+                .into());
+            }
+            let mut indices = vec![None; axes.len()];
+            for (name, coordinate) in values {
+                let axis = self.axis_index(name)?;
+                let idx = coordinate_index(axes[axis].as_ref(), coordinate)?;
+                if indices[axis].replace(idx).is_some() {
+                    return Err(HistError::DuplicateAxis(axis).into());
+                }
+            }
+            let indices = indices
+                .into_iter()
+                .enumerate()
+                .map(|(axis, idx)| idx.ok_or(HistError::MissingAxisValue(axis)))
+                .collect::<Result<Vec<usize>, HistError>>()?;
+            self.fill(&indices, weight)
+        }
+
+        // sum over all axes not in `keep` (including their flow bins)
+        // the kept axes appear in the order given by `keep`
+        fn project(&self, keep: &[usize]) -> Result<Self>
+        where
+            Self: Sized,
+        {
+            let axes = self.get_axes();
+            for (i, &axis) in keep.iter().enumerate() {
+                if axis >= axes.len() {
+                    return Err(HistError::AxisOutOfRange {
+                        axis,
+                        naxes: axes.len(),
+                    }
+                    .into());
+                }
+                if keep[..i].contains(&axis) {
+                    return Err(HistError::DuplicateAxis(axis).into());
+                }
+            }
+            let mut projected = self.empty_like(keep.iter().map(|&i| axes[i].clone()).collect());
+            for (idx, bin) in self.iter_bins() {
+                let indices = self.unravel_index(idx);
+                let kept: Vec<usize> = keep.iter().map(|&i| indices[i]).collect();
+                let new_idx = projected.stride_index(&kept)?;
+                try_update_bin(&mut projected, new_idx, |b| b.merge(&bin))?;
+            }
+            Ok(projected)
+        }
+
+        // checks that `other` has compatible axes, categorical axes may be reordered
+        fn check_compatible(&self, other: &dyn Histogram<Storage = Self::Storage>) -> Result<()> {
+            self.axis_index_maps(other).map(|_| ())
+        }
+
+        // per axis: maps the indices of `other` to indices of this histogram
+        fn axis_index_maps(
+            &self,
+            other: &dyn Histogram<Storage = Self::Storage>,
+        ) -> Result<Vec<Vec<usize>>> {
+            let (axes, other_axes) = (self.get_axes(), other.get_axes());
+            if axes.len() != other_axes.len() {
+                return Err(HistError::AxesValuesMismatch {
+                    nvalues: other_axes.len(),
+                    naxes: axes.len(),
+                }
+                .into());
+            }
+            let mut maps = Vec::with_capacity(axes.len());
+            for (i, (axis, other_axis)) in axes.iter().zip(other_axes.iter()).enumerate() {
+                let map = axis.index_map(other_axis.as_ref()).map_err(|source| {
+                    HistError::AxisMismatch {
+                        axis: i,
+                        name: axis.name().map(str::to_string),
+                        source,
+                    }
+                })?;
+                maps.push(map);
+            }
+            Ok(maps)
+        }
+
+        // adds the contents of `other` to this histogram
+        fn merge(&mut self, other: &dyn Histogram<Storage = Self::Storage>) -> Result<()> {
+            let maps = self.axis_index_maps(other)?;
+            add_remapped(other, self, &maps)
+        }
+
+        // merges bins of the categorical axis `axis` into new categories:
+        // `groups` maps each new label to the old labels it is made of,
+        // contents of old bins that are not part of any group end up in the overflow bin
+        // (Integer axes keep their labels sorted)
+        fn regroup<L>(&self, axis: usize, groups: &[(L, Vec<L>)]) -> Result<Self>
+        where
+            Self: Sized,
+            L: Clone + Into<Value>,
+        {
+            let axes = self.get_axes();
+            let old = axes.get(axis).ok_or(HistError::AxisOutOfRange {
+                axis,
+                naxes: axes.len(),
+            })?;
+            let mut groups: Vec<(Value, Vec<Value>)> = groups
+                .iter()
+                .map(|(new, olds)| {
+                    let olds = olds.iter().cloned().map(Into::into).collect();
+                    (new.clone().into(), olds)
+                })
+                .collect();
+
+            let mut new_axis: Box<dyn Axis> = match old.kind() {
+                "Category" => {
+                    let labels = groups
+                        .iter()
+                        .map(|(label, _)| match label {
+                            Value::Str(label) => Ok(label.clone()),
+                            Value::Int(_) => Err(AxisError::InvalidValueType),
+                        })
+                        .collect::<Result<Vec<String>, AxisError>>()?;
+                    Box::new(Category::new(labels)?)
+                }
+                "Integer" => {
+                    groups.sort_by_key(|(label, _)| match label {
+                        Value::Int(label) => Some(*label),
+                        Value::Str(_) => None,
+                    });
+                    let labels = groups
+                        .iter()
+                        .map(|(label, _)| match label {
+                            Value::Int(label) => Ok(*label),
+                            Value::Str(_) => Err(AxisError::InvalidValueType),
+                        })
+                        .collect::<Result<Vec<i32>, AxisError>>()?;
+                    Box::new(Integer::new(labels)?)
+                }
+                _ => return Err(HistError::NotCategorical(axis).into()),
+            };
+            *new_axis.metadata_mut() = old.metadata().clone();
+
+            // maps old indices to new indices
+            let old_lookup: HashMap<Value, usize> = (0..old.num_bins(true))
+                .filter_map(|idx| old.value(idx).map(|value| (value, idx)))
+                .collect();
+            let mut map = vec![None; old.num_bins(true)];
+            for (new_idx, (_, olds)) in (0..new_axis.num_bins(true))
+                .filter(|&idx| idx != new_axis.overflow())
+                .zip(groups.iter())
+            {
+                for value in olds {
+                    let old_idx = *old_lookup
+                        .get(value)
+                        .ok_or_else(|| HistError::UnknownCategory(value.clone()))?;
+                    if map[old_idx].replace(new_idx).is_some() {
+                        return Err(HistError::DuplicateCategory(value.clone()).into());
+                    }
+                }
+            }
+            let map: Vec<usize> = map
+                .into_iter()
+                .map(|idx| idx.unwrap_or(new_axis.overflow()))
+                .collect();
+
+            let mut maps: Vec<Vec<usize>> = axes
+                .iter()
+                .map(|axis| (0..axis.num_bins(true)).collect())
+                .collect();
+            maps[axis] = map;
+            let mut new_axes = axes.clone();
+            new_axes[axis] = new_axis;
+            let mut regrouped = self.empty_like(new_axes);
+            add_remapped(self, &mut regrouped, &maps)?;
+            Ok(regrouped)
+        }
+
+        fn project_by_name(&self, keep: &[&str]) -> Result<Self>
+        where
+            Self: Sized,
+        {
+            let keep = keep
+                .iter()
+                .map(|name| self.axis_index(name))
+                .collect::<Result<Vec<usize>>>()?;
+            self.project(&keep)
+        }
+
+        // bins at `index` (including flow bins) along `axis`, the axis itself is dropped
+        fn slice(&self, axis: usize, index: usize) -> Result<Self>
+        where
+            Self: Sized,
+        {
+            let axes = self.get_axes();
+            let nbins = axes
+                .get(axis)
+                .ok_or(HistError::AxisOutOfRange {
+                    axis,
+                    naxes: axes.len(),
+                })?
+                .num_bins(true);
+            if index >= nbins {
+                return Err(HistError::IndexOutOfRange { axis, index, nbins }.into());
+            }
+            let mut kept = axes.clone();
+            kept.remove(axis);
+            let mut sliced = self.empty_like(kept);
+            for (idx, bin) in self.iter_bins() {
+                let mut indices = self.unravel_index(idx);
+                if indices.remove(axis) == index {
+                    let new_idx = sliced.stride_index(&indices)?;
+                    try_update_bin(&mut sliced, new_idx, |b| b.merge(&bin))?;
+                }
+            }
+            Ok(sliced)
+        }
+
+        fn slice_by_name(&self, name: &str, index: usize) -> Result<Self>
+        where
+            Self: Sized,
+        {
+            self.slice(self.axis_index(name)?, index)
+        }
+
+        // sum of all bin values, with or without flow bins
+        fn sum(&self, flow: bool) -> f64 {
+            self.values(flow).iter().sum()
+        }
+
+        // bin values of the regular bins of `axis`, summed over all other axes (including flow)
+        fn marginal(&self, axis: usize) -> Result<Vec<f64>> {
+            let axes = self.get_axes();
+            let marginal_axis = axes.get(axis).ok_or(HistError::AxisOutOfRange {
+                axis,
+                naxes: axes.len(),
+            })?;
+            let mut position = vec![None; marginal_axis.num_bins(true)];
+            for (i, idx) in marginal_axis.display_order(false).into_iter().enumerate() {
+                position[idx] = Some(i);
+            }
+            let mut marginal = vec![0.0; marginal_axis.num_bins(false)];
+            for (idx, bin) in self.iter_bins() {
+                if let Some(i) = position[self.unravel_index(idx)[axis]] {
+                    marginal[i] += bin.value();
+                }
+            }
+            Ok(marginal)
+        }
+
+        // mean of the marginal distribution along the continuous `axis` (from bin centers)
+        fn mean(&self, axis: usize) -> Result<f64> {
+            let (centers, _, weights) = continuous_marginal(self, axis)?;
+            Ok(weighted_mean(&centers, &weights))
+        }
+
+        // standard deviation of the marginal distribution along the continuous `axis`
+        fn std(&self, axis: usize) -> Result<f64> {
+            let (centers, _, weights) = continuous_marginal(self, axis)?;
+            Ok(central_moment(&centers, &weights, 2).sqrt())
+        }
+
+        // skewness of the marginal distribution along the continuous `axis`
+        fn skewness(&self, axis: usize) -> Result<f64> {
+            let (centers, _, weights) = continuous_marginal(self, axis)?;
+            let std = central_moment(&centers, &weights, 2).sqrt();
+            Ok(central_moment(&centers, &weights, 3) / std.powi(3))
+        }
+
+        fn median(&self, axis: usize, interpolation: Interpolation) -> Result<f64> {
+            self.quantile(axis, 0.5, interpolation)
+        }
+
+        // `q`-quantile of the marginal distribution along the continuous `axis`,
+        // flow bins are not included
+        fn quantile(&self, axis: usize, q: f64, interpolation: Interpolation) -> Result<f64> {
+            if !(0.0..=1.0).contains(&q) {
+                return Err(HistError::InvalidQuantile(q).into());
+            }
+            let (centers, edges, weights) = continuous_marginal(self, axis)?;
+            let target = q * weights.iter().sum::<f64>();
+            let mut cumulative = 0.0;
+            for (i, &weight) in weights.iter().enumerate() {
+                if weight > 0.0 && cumulative + weight >= target {
+                    return Ok(match interpolation {
+                        Interpolation::Center => centers[i],
+                        Interpolation::Linear => {
+                            let fraction = (target - cumulative) / weight;
+                            edges[i] + fraction * (edges[i + 1] - edges[i])
+                        }
+                    });
+                }
+                cumulative += weight;
+            }
+            // empty histogram
+            Ok(f64::NAN)
+        }
+
+        // probability density of the regular bins: values divided by the total (without flow)
+        // and the bin volume, categorical axes have unit width; (values, variances) as flat,
+        // row-major arrays of `shape(false)` as in `values`, with Poisson variances for
+        // storages that only keep the sum of weights
+        fn density(&self) -> Result<(Vec<f64>, Vec<f64>)> {
+            let values = self.values(false);
+            let total: f64 = values.iter().sum();
+            if total == 0.0 {
+                return Err(HistError::ZeroSum.into());
+            }
+            // bin volumes in the same (row-major) order
+            let volumes = self.get_axes().iter().fold(vec![1.0], |volumes, axis| {
+                let widths = axis.widths();
+                volumes
+                    .iter()
+                    .flat_map(|volume| widths.iter().map(move |width| volume * width))
+                    .collect()
+            });
+            let scales: Vec<f64> = volumes
+                .iter()
+                .map(|volume| 1.0 / (total * volume))
+                .collect();
+            Ok((
+                values.iter().zip(&scales).map(|(v, s)| v * s).collect(),
+                self.variances(false)
+                    .iter()
+                    .zip(&scales)
+                    .map(|(v, s)| v * s * s)
+                    .collect(),
+            ))
+        }
+
+        // cumulative sums along `axis` (for each bin of the other axes),
+        // with `flow` the sums start at the underflow (`Forward`) or overflow (`Backward`) bin
+        // and end in the opposite flow bin, otherwise flow bins keep their contents
+        fn cumulative(&self, axis: usize, direction: Direction, flow: bool) -> Result<Self>
+        where
+            Self: Sized,
+        {
+            let axes = self.get_axes();
+            let cumulative_axis = axes.get(axis).ok_or(HistError::AxisOutOfRange {
+                axis,
+                naxes: axes.len(),
+            })?;
+            let mut order = cumulative_axis.display_order(flow);
+            if direction == Direction::Backward {
+                order.reverse();
+            }
+            // position in the sum by axis index, `None` for flow bins that are not summed
+            let mut position = vec![None; cumulative_axis.num_bins(true)];
+            for (i, &idx) in order.iter().enumerate() {
+                position[idx] = Some(i);
+            }
+            let stride: usize = axes[axis + 1..]
+                .iter()
+                .map(|axis| axis.num_bins(true))
+                .product();
+
+            // stored bins of each line along `axis`, keyed by the index of the line's bin 0
+            let mut cumulative = self.empty_like(axes.clone());
+            let mut lines: BTreeMap<usize, Vec<(usize, Self::Storage)>> = BTreeMap::new();
+            for (idx, bin) in self.iter_bins() {
+                let line_idx = self.unravel_index(idx)[axis];
+                match position[line_idx] {
+                    Some(i) => lines
+                        .entry(idx - line_idx * stride)
+                        .or_default()
+                        .push((i, bin)),
+                    None => cumulative.update_bin(idx, &mut |b| *b = bin.clone()),
+                }
+            }
+            for (first, mut bins) in lines {
+                bins.sort_by_key(|(i, _)| *i);
+                let mut sum = bins[0].1.zero();
+                let mut bins = bins.into_iter().peekable();
+                // bins before the first stored bin stay empty
+                for (i, &line_idx) in order.iter().enumerate().skip(bins.peek().unwrap().0) {
+                    if let Some((_, bin)) = bins.next_if(|(j, _)| *j == i) {
+                        sum.merge(&bin)?;
+                    }
+                    cumulative.update_bin(first + line_idx * stride, &mut |b| *b = sum.clone());
+                }
+            }
+            Ok(cumulative)
+        }
+    }
+
+    // variation `k` of a multi-weight histogram filled into `empty`, which has the same axes
+    // and the storage of the variation (see `MultiWeight::variation`)
+    pub fn variation_of<H, O>(hist: &H, k: usize, mut empty: O) -> Result<O>
+    where
+        H: Histogram<Storage = MultiWeight> + ?Sized,
+        O: Histogram<Storage = Storage>,
+    {
+        for (idx, bin) in hist.iter_bins() {
+            let bin = bin.variation(k)?;
+            empty.update_bin(idx, &mut |b| *b = bin);
+        }
+        Ok(empty)
+    }
+
+    // checks that `other` has the same axes as `axes` (same order of categories)
+    pub fn check_equal_axes(axes: &[Box<dyn Axis>], other: &[Box<dyn Axis>]) -> Result<()> {
+        if axes.len() != other.len() {
+            return Err(HistError::AxesValuesMismatch {
+                nvalues: other.len(),
+                naxes: axes.len(),
+            }
+            .into());
+        }
+        for (i, (axis, other_axis)) in axes.iter().zip(other.iter()).enumerate() {
+            axis.check_equal(other_axis.as_ref())
+                .map_err(|source| HistError::AxisMismatch {
+                    axis: i,
+                    name: axis.name().map(str::to_string),
+                    source,
+                })?;
+        }
+        Ok(())
+    }
+
+    // (bin centers, bin edges, marginal bin values) of the continuous `axis`
+    fn continuous_marginal<H>(hist: &H, axis: usize) -> Result<(Vec<f64>, Vec<f64>, Vec<f64>)>
+    where
+        H: Histogram + ?Sized,
+    {
+        let weights = hist.marginal(axis)?;
+        let marginal_axis = &hist.get_axes()[axis];
+        if marginal_axis
+            .bins()
+            .iter()
+            .any(|bin| !matches!(bin, Bin::Interval(_)))
+        {
+            return Err(AxisError::ExpectedContinuousAxis.into());
+        }
+        Ok((marginal_axis.centers(), marginal_axis.edges(), weights))
+    }
+
+    // bin index of `coordinate` along `axis`, see `Histogram::fill_by_name`
+    fn coordinate_index(axis: &dyn Axis, coordinate: &Coordinate) -> Result<usize, AxisError> {
+        let continuous = axis
+            .bins()
+            .iter()
+            .all(|bin| matches!(bin, Bin::Interval(_)));
+        match coordinate {
+            Coordinate::Continuous(x) if continuous => {
+                let edges = axis.edges();
+                if *x < edges[0] {
+                    Ok(axis.underflow())
+                } else if *x >= edges[edges.len() - 1] {
+                    Ok(axis.overflow())
+                } else {
+                    Ok(edges.partition_point(|edge| edge <= x) - 1)
+                }
+            }
+            Coordinate::Discrete(value) if !continuous => Ok((0..axis.num_bins(true))
+                .find(|&idx| axis.value(idx).as_ref() == Some(value))
+                .unwrap_or(axis.overflow())),
+            _ => Err(AxisError::InvalidValueType),
+        }
+    }
+
+    fn weighted_mean(values: &[f64], weights: &[f64]) -> f64 {
+        let sum: f64 = weights.iter().sum();
+        values.iter().zip(weights).map(|(v, w)| v * w).sum::<f64>() / sum
+    }
+
+    // k-th central moment
+    fn central_moment(values: &[f64], weights: &[f64], k: i32) -> f64 {
+        let mean = weighted_mean(values, weights);
+        let sum: f64 = weights.iter().sum();
+        values
+            .iter()
+            .zip(weights)
+            .map(|(v, w)| w * (v - mean).powi(k))
+            .sum::<f64>()
+            / sum
+    }
+
+    // applies the fallible `f` to the bin at the stride index `idx`,
+    // the bin is only stored on success (sparse backends keep no empty bin after an error)
+    fn try_update_bin<H, F>(hist: &mut H, idx: usize, f: F) -> Result<()>
+    where
+        H: Histogram + ?Sized,
+        F: FnOnce(&mut H::Storage) -> Result<(), StorageError>,
+    {
+        let mut updated = hist.get_bin(idx);
+        f(&mut updated)?;
+        let mut updated = Some(updated);
+        hist.update_bin(idx, &mut |bin| {
+            if let Some(updated) = updated.take() {
+                *bin = updated;
+            }
+        });
+        Ok(())
+    }
+
+    // applies `f` to all bins in the order described in `Histogram::values`
+    fn bin_view<H, F>(hist: &H, flow: bool, f: F) -> Vec<f64>
+    where
+        H: Histogram + ?Sized,
+        F: Fn(&H::Storage) -> f64,
+    {
+        let axes = hist.get_axes();
+        let orders: Vec<Vec<usize>> = axes.iter().map(|axis| axis.display_order(flow)).collect();
+        let size = orders.iter().map(Vec::len).product();
+        let mut view = Vec::with_capacity(size);
+        let mut pos = vec![0; orders.len()];
+        for _ in 0..size {
+            let idx = axes
+                .iter()
+                .zip(orders.iter().zip(pos.iter()))
+                .fold(0, |idx, (axis, (order, &p))| {
+                    idx * axis.num_bins(true) + order[p]
+                });
+            view.push(f(&hist.get_bin(idx)));
+            // next position, the last axis runs fastest
+            for (p, order) in pos.iter_mut().zip(orders.iter()).rev() {
+                *p += 1;
+                if *p < order.len() {
+                    break;
+                }
+                *p = 0;
+            }
+        }
+        view
+    }
+
+    // adds all bins of `src` to `dst`, `maps` translates the indices of each axis
+    fn add_remapped<H>(
+        src: &dyn Histogram<Storage = H::Storage>,
+        dst: &mut H,
+        maps: &[Vec<usize>],
+    ) -> Result<()>
+    where
+        H: Histogram + ?Sized,
+    {
+        for (idx, bin) in src.iter_bins() {
+            let indices: Vec<usize> = src
+                .unravel_index(idx)
+                .iter()
+                .zip(maps.iter())
+                .map(|(&i, map)| map[i])
+                .collect();
+            let new_idx = dst.stride_index(&indices)?;
+            try_update_bin(dst, new_idx, |b| b.merge(&bin))?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,151 @@
+// Small dense linear algebra for the fitting and unfolding modules
+// (row-major `Vec<Vec<f64>>` matrices)
+
+// solves `matrix * x = rhs` by Gaussian elimination with partial pivoting,
+// `None` if the matrix is singular
+pub fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        if matrix[pivot][col] == 0.0 || !matrix[pivot][col].is_finite() {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (row, lower_row) in lower.iter_mut().enumerate() {
+            let factor = lower_row[col] / pivot_row[col];
+            for (value, pivot_value) in lower_row[col..].iter_mut().zip(pivot_row[col..].iter()) {
+                *value -= factor * pivot_value;
+            }
+            rhs[col + 1 + row] -= factor * rhs[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| matrix[row][k] * x[k]).sum();
+        x[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(x)
+}
+
+// inverse of a square matrix, `None` if it is singular
+pub fn invert(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let columns = (0..n)
+        .map(|col| {
+            let unit = (0..n)
+                .map(|row| if row == col { 1.0 } else { 0.0 })
+                .collect();
+            solve(matrix.to_vec(), unit)
+        })
+        .collect::<Option<Vec<Vec<f64>>>>()?;
+    Some(transpose(&columns))
+}
+
+pub fn transpose(matrix: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let ncols = matrix.first().map_or(0, Vec::len);
+    (0..ncols)
+        .map(|col| matrix.iter().map(|row| row[col]).collect())
+        .collect()
+}
+
+// thin singular value decomposition `matrix = U diag(s) V^T` of an m x n matrix with m >= n
+// (one-sided Jacobi rotations), singular values in descending order
+pub struct Svd {
+    pub u: Vec<Vec<f64>>,
+    pub s: Vec<f64>,
+    pub v: Vec<Vec<f64>>,
+}
+
+pub fn svd(matrix: &[Vec<f64>]) -> Svd {
+    const MAX_SWEEPS: usize = 100;
+    let (m, n) = (matrix.len(), matrix.first().map_or(0, Vec::len));
+    // columns of the working matrix and of V
+    let mut a = transpose(matrix);
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let dot = |x: &[f64], y: &[f64]| x.iter().zip(y).map(|(x, y)| x * y).sum::<f64>();
+                let (alpha, beta, gamma) =
+                    (dot(&a[p], &a[p]), dot(&a[q], &a[q]), dot(&a[p], &a[q]));
+                if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2.0 * gamma);
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                for columns in [&mut a, &mut v] {
+                    let (left, right) = columns.split_at_mut(q);
+                    for (x, y) in left[p].iter_mut().zip(right[0].iter_mut()) {
+                        (*x, *y) = (c * *x - s * *y, s * *x + c * *y);
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let norms: Vec<f64> = a
+        .iter()
+        .map(|column| column.iter().map(|x| x * x).sum::<f64>().sqrt())
+        .collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+    let u_columns: Vec<Vec<f64>> = order
+        .iter()
+        .map(|&i| {
+            a[i].iter()
+                .map(|x| if norms[i] > 0.0 { x / norms[i] } else { 0.0 })
+                .collect()
+        })
+        .collect();
+    let v_columns: Vec<Vec<f64>> = order.iter().map(|&i| v[i].clone()).collect();
+    let u = if n == 0 {
+        vec![Vec::new(); m]
+    } else {
+        transpose(&u_columns)
+    };
+    Svd {
+        u,
+        s: order.iter().map(|&i| norms[i]).collect(),
+        v: transpose(&v_columns),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linalg() {
+        let matrix = vec![vec![0.0, 2.0], vec![4.0, 1.0]];
+        assert_eq!(solve(matrix.clone(), vec![2.0, 9.0]), Some(vec![2.0, 1.0]));
+        let inverse = invert(&matrix).unwrap();
+        assert_eq!(inverse, vec![vec![-0.125, 0.25], vec![0.5, 0.0]]);
+        assert!(invert(&[vec![1.0, 2.0], vec![2.0, 4.0]]).is_none());
+        assert_eq!(transpose(&[vec![1.0, 2.0]]), vec![vec![1.0], vec![2.0]]);
+
+        let matrix = vec![vec![3.0, 1.0], vec![1.0, 3.0], vec![0.0, 2.0]];
+        let Svd { u, s, v } = svd(&matrix);
+        assert!(s[0] >= s[1]);
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                let product: f64 = (0..2).map(|k| u[i][k] * s[k] * v[j][k]).sum();
+                assert!((product - value).abs() < 1e-12);
+            }
+        }
+        // singular values are the square roots of the eigenvalues of A^T A = [[10, 6], [6, 14]]
+        assert!((s[0] * s[0] - (12.0 + 40f64.sqrt())).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,264 @@
+// Evaluation of a filled histogram as a lookup table (e.g. scale factors binned in pT and eta)
+//
+// Coordinates outside of the range of a continuous axis are handled by the `OutOfRange` policy,
+// optionally the table is interpolated multilinearly between the bin centers of `Uniform`
+// and `Variable` axes (constant beyond the outermost centers).
+use crate::hist::Histogram;
+use crate::sampler::Coordinate;
+use anyhow::Result;
+use hist_axes::axis::Axis;
+use hist_axes::bin::{Bin, Value};
+use hist_storages::StorageElement;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LookupError {
+    #[error("got {ncoords} coordinates for {naxes} axes")]
+    CoordinatesMismatch { ncoords: usize, naxes: usize },
+    #[error("coordinate {coordinate:?} outside of the range of axis {axis}")]
+    OutOfRange { axis: usize, coordinate: Coordinate },
+    #[error("expected a {expected} coordinate for axis {axis}")]
+    WrongCoordinateKind { axis: usize, expected: &'static str },
+    #[error("axis {axis} out of range for a lookup table with {naxes} axes")]
+    AxisOutOfRange { axis: usize, naxes: usize },
+    #[error("cannot interpolate along axis {axis} of kind {kind}")]
+    NotInterpolable { axis: usize, kind: &'static str },
+    #[error("column of length {got} for {expected} rows")]
+    ColumnLengthMismatch { expected: usize, got: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfRange {
+    // values beyond the axis range use the first or last bin, unknown labels are an error
+    Clamp,
+    // values beyond the axis range use the flow bins, unknown labels the overflow bin
+    Flow,
+    // values beyond the axis range and unknown labels are an error
+    Error,
+}
+
+// Column of coordinates for a single axis
+#[derive(Debug, Clone, Copy)]
+pub enum Column<'a> {
+    Values(&'a [f64]),
+    Labels(&'a [Value]),
+}
+
+impl Column<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Column::Values(values) => values.len(),
+            Column::Labels(labels) => labels.len(),
+        }
+    }
+
+    fn get(&self, row: usize) -> Coordinate {
+        match self {
+            Column::Values(values) => Coordinate::Continuous(values[row]),
+            Column::Labels(labels) => Coordinate::Discrete(labels[row].clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    axes: Vec<Box<dyn Axis>>,
+    // bin edges and centers of continuous axes (empty for categorical axes)
+    edges: Vec<Vec<f64>>,
+    centers: Vec<Vec<f64>>,
+    // bin index of each label of categorical axes
+    labels: Vec<HashMap<Value, usize>>,
+    // bin values by stride index
+    values: Vec<f64>,
+    policy: OutOfRange,
+    interpolate: Vec<bool>,
+}
+
+impl Lookup {
+    // bin values of `hist`, clamped to the axis ranges and without interpolation
+    pub fn new<H: Histogram + ?Sized>(hist: &H) -> Self {
+        let axes = hist.get_axes().clone();
+        let mut values = vec![0.0; hist.num_bins(true)];
+        for (idx, bin) in hist.iter_bins() {
+            values[idx] = bin.value();
+        }
+        let continuous = |axis: &dyn Axis| {
+            axis.bins()
+                .iter()
+                .all(|bin| matches!(bin, Bin::Interval(_)))
+        };
+        Self {
+            edges: axes
+                .iter()
+                .map(|axis| {
+                    if continuous(axis.as_ref()) {
+                        axis.edges()
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect(),
+            centers: axes
+                .iter()
+                .map(|axis| {
+                    if continuous(axis.as_ref()) {
+                        axis.centers()
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect(),
+            labels: axes
+                .iter()
+                .map(|axis| {
+                    (0..axis.num_bins(true))
+                        .filter_map(|idx| axis.value(idx).map(|value| (value, idx)))
+                        .collect()
+                })
+                .collect(),
+            interpolate: vec![false; axes.len()],
+            values,
+            axes,
+            policy: OutOfRange::Clamp,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: OutOfRange) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // interpolates between bin centers along the given `Uniform` or `Variable` axes
+    pub fn with_interpolation(mut self, axes: &[usize]) -> Result<Self> {
+        for &axis in axes {
+            let kind = self
+                .axes
+                .get(axis)
+                .ok_or(LookupError::AxisOutOfRange {
+                    axis,
+                    naxes: self.axes.len(),
+                })?
+                .kind();
+            if !matches!(kind, "Uniform" | "Variable") {
+                return Err(LookupError::NotInterpolable { axis, kind }.into());
+            }
+            self.interpolate[axis] = true;
+        }
+        Ok(self)
+    }
+
+    pub fn evaluate(&self, coordinates: &[Coordinate]) -> Result<f64> {
+        if coordinates.len() != self.axes.len() {
+            return Err(LookupError::CoordinatesMismatch {
+                ncoords: coordinates.len(),
+                naxes: self.axes.len(),
+            }
+            .into());
+        }
+        // (bin index, weight) pairs of each axis
+        let mut terms = vec![(0, 1.0)];
+        for (axis, coordinate) in coordinates.iter().enumerate() {
+            let nbins = self.axes[axis].num_bins(true);
+            let points = self.locate(axis, coordinate)?;
+            terms = terms
+                .iter()
+                .flat_map(|&(idx, weight)| {
+                    points
+                        .iter()
+                        .map(move |&(i, w)| (idx * nbins + i, weight * w))
+                })
+                .collect();
+        }
+        Ok(terms
+            .iter()
+            .map(|&(idx, weight)| weight * self.values[idx])
+            .sum())
+    }
+
+    // evaluates row by row, one column per axis
+    pub fn evaluate_columns(&self, columns: &[Column]) -> Result<Vec<f64>> {
+        let nrows = columns.first().map_or(0, Column::len);
+        for column in columns {
+            if column.len() != nrows {
+                return Err(LookupError::ColumnLengthMismatch {
+                    expected: nrows,
+                    got: column.len(),
+                }
+                .into());
+            }
+        }
+        (0..nrows)
+            .map(|row| {
+                let coordinates: Vec<Coordinate> =
+                    columns.iter().map(|column| column.get(row)).collect();
+                self.evaluate(&coordinates)
+            })
+            .collect()
+    }
+
+    // bin indices along `axis` with their interpolation weights
+    fn locate(&self, axis: usize, coordinate: &Coordinate) -> Result<Vec<(usize, f64)>> {
+        let out_of_range = || LookupError::OutOfRange {
+            axis,
+            coordinate: coordinate.clone(),
+        };
+        let edges = &self.edges[axis];
+        match coordinate {
+            Coordinate::Discrete(value) => {
+                if !edges.is_empty() {
+                    return Err(LookupError::WrongCoordinateKind {
+                        axis,
+                        expected: "continuous",
+                    }
+                    .into());
+                }
+                match (self.labels[axis].get(value), self.policy) {
+                    (Some(&idx), _) => Ok(vec![(idx, 1.0)]),
+                    (None, OutOfRange::Flow) => Ok(vec![(self.axes[axis].overflow(), 1.0)]),
+                    (None, _) => Err(out_of_range().into()),
+                }
+            }
+            Coordinate::Continuous(x) => {
+                if edges.is_empty() {
+                    return Err(LookupError::WrongCoordinateKind {
+                        axis,
+                        expected: "discrete",
+                    }
+                    .into());
+                }
+                let (low, high) = (edges[0], edges[edges.len() - 1]);
+                let x = if x.is_nan() {
+                    return Err(out_of_range().into());
+                } else if *x < low || *x > high {
+                    match self.policy {
+                        OutOfRange::Clamp => x.clamp(low, high),
+                        OutOfRange::Flow if *x < low => {
+                            return Ok(vec![(self.axes[axis].underflow(), 1.0)])
+                        }
+                        OutOfRange::Flow => return Ok(vec![(self.axes[axis].overflow(), 1.0)]),
+                        OutOfRange::Error => return Err(out_of_range().into()),
+                    }
+                } else {
+                    *x
+                };
+                if !self.interpolate[axis] {
+                    // bins include their lower edge, the last bin also its upper edge
+                    let idx = edges.partition_point(|&edge| edge <= x) - 1;
+                    return Ok(vec![(idx.min(edges.len() - 2), 1.0)]);
+                }
+                let centers = &self.centers[axis];
+                let last = centers.len() - 1;
+                if x <= centers[0] {
+                    return Ok(vec![(0, 1.0)]);
+                }
+                if x >= centers[last] {
+                    return Ok(vec![(last, 1.0)]);
+                }
+                let upper = centers.partition_point(|&center| center <= x);
+                let t = (x - centers[upper - 1]) / (centers[upper] - centers[upper - 1]);
+                Ok(vec![(upper - 1, 1.0 - t), (upper, t)])
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// Random sampling from a histogram used as a (binned) PDF, e.g. to generate toy datasets
+//
+// A cumulative table over the regular bins is built once, a bin is drawn by binary search
+// and the coordinates are drawn uniformly within the intervals of the bin
+// (categorical axes return the label of the bin).
+// The random numbers come from a user-supplied `rand::Rng` for reproducibility.
+use crate::hist::{HistError, Histogram};
+use anyhow::Result;
+use hist_axes::axis::Axis;
+use hist_axes::bin::{Bin, Value};
+use hist_storages::StorageElement;
+use rand::{Rng, RngExt};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SamplerError {
+    #[error("bin {bin} has a negative or non-finite content ({value})")]
+    InvalidBinContent { bin: usize, value: f64 },
+}
+
+// Coordinate along a single axis
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coordinate {
+    Continuous(f64),
+    Discrete(Value),
+}
+
+impl From<f64> for Coordinate {
+    fn from(value: f64) -> Self {
+        Coordinate::Continuous(value)
+    }
+}
+
+impl From<i32> for Coordinate {
+    fn from(value: i32) -> Self {
+        Coordinate::Discrete(Value::Int(value))
+    }
+}
+
+impl From<&str> for Coordinate {
+    fn from(value: &str) -> Self {
+        Coordinate::Discrete(value.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    axes: Vec<Box<dyn Axis>>,
+    // per-axis indices of the bins with a positive content
+    indices: Vec<Vec<usize>>,
+    // normalised cumulative sum of the contents of these bins
+    cumulative: Vec<f64>,
+}
+
+impl Sampler {
+    // flow bins are never sampled, the bin contents must not be negative
+    pub fn new<H: Histogram + ?Sized>(hist: &H) -> Result<Self> {
+        let axes = hist.get_axes();
+        // stored bins in index order, so the table does not depend on the backend
+        let mut bins: Vec<_> = hist.iter_bins().collect();
+        bins.sort_by_key(|(idx, _)| *idx);
+        let (mut indices, mut cumulative) = (Vec::new(), Vec::new());
+        let mut total = 0.0;
+        for (idx, bin) in bins {
+            let bin_indices = hist.unravel_index(idx);
+            let regular = axes
+                .iter()
+                .zip(bin_indices.iter())
+                .all(|(axis, &i)| axis.bin(i).is_some());
+            if !regular {
+                continue;
+            }
+            let value = bin.value();
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(SamplerError::InvalidBinContent { bin: idx, value }.into());
+            }
+            if value > 0.0 {
+                total += value;
+                indices.push(bin_indices);
+                cumulative.push(total);
+            }
+        }
+        if total == 0.0 {
+            return Err(HistError::ZeroSum.into());
+        }
+        cumulative.iter_mut().for_each(|c| *c /= total);
+        Ok(Self {
+            axes: axes.clone(),
+            indices,
+            cumulative,
+        })
+    }
+
+    // per-axis indices of a random bin
+    pub fn sample_bin<R: Rng + ?Sized>(&self, rng: &mut R) -> &[usize] {
+        let u: f64 = rng.random();
+        let pos = self.cumulative.partition_point(|&c| c <= u);
+        // guards against rounding of the last cumulative value
+        &self.indices[pos.min(self.indices.len() - 1)]
+    }
+
+    // coordinates of a random point, one per axis
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Coordinate> {
+        let bin = self.sample_bin(rng);
+        self.axes
+            .iter()
+            .zip(bin.iter())
+            .map(|(axis, &i)| match axis.bin(i) {
+                Some(Bin::Interval(interval)) => {
+                    let u: f64 = rng.random();
+                    Coordinate::Continuous(interval.low + u * interval.width())
+                }
+                Some(Bin::SingleValue(single)) => Coordinate::Discrete(single.value),
+                None => unreachable!("flow bins are not sampled"),
+            })
+            .collect()
+    }
+
+    pub fn sample_n<R: Rng + ?Sized>(&self, rng: &mut R, n: usize) -> Vec<Vec<Coordinate>> {
+        (0..n).map(|_| self.sample(rng)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_sampler_table() {
+        let sampler = Sampler {
+            axes: Vec::new(),
+            indices: vec![vec![0], vec![2]],
+            cumulative: vec![0.25, 1.0 - 1e-17],
+        };
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(42);
+        let draws = 10_000;
+        let first = (0..draws)
+            .filter(|_| sampler.sample_bin(&mut rng) == [0])
+            .count();
+        assert!((first as f64 / draws as f64 - 0.25).abs() < 0.02);
+    }
+}
@@ -0,0 +1,394 @@
+// Unfolding of detector-level (reco) distributions to truth level
+//
+// The response is a 2-D histogram with the axes (reco, truth):
+// - matched events fill the regular bins of both axes
+// - fakes (reconstructed without a truth match) fill a truth flow bin
+// - misses (not reconstructed within the reco range) fill a reco flow bin
+// Fakes are removed from the data with the fake fraction of each reco bin, the truth
+// spectrum of the response (including misses) is the prior and reference of the unfolding.
+//
+// The covariances propagate the statistical uncertainties of the data only (through all
+// iterations for the Bayesian unfolding), the response is treated as exact.
+// The returned histograms are set to the unfolded values with the diagonal of the covariance
+// as variances, so the storage of the response must support `StorageElement::set`
+// (floating-point and weighted storages, not integer or profile storages).
+use crate::hist::{check_equal_axes, Histogram};
+use crate::linalg::{invert, svd, transpose, Svd};
+use anyhow::Result;
+use hist_storages::StorageElement;
+use thiserror::Error;
+
+// small diagonal term that makes the curvature matrix invertible (Hoecker & Kartvelishvili)
+const CURVATURE_REGULATOR: f64 = 1e-3;
+
+#[derive(Error, Debug)]
+pub enum UnfoldError {
+    #[error("expected a 2-D (reco, truth) histogram, got {0} axes")]
+    NotTwoDimensional(usize),
+    #[error("iterative unfolding needs at least one iteration")]
+    NoIterations,
+    #[error("regularization {k} outside of [1, {nbins}]")]
+    InvalidRegularization { k: usize, nbins: usize },
+    #[error("singular curvature matrix")]
+    SingularCurvature,
+}
+
+// Migration probabilities and corrections of a response, regular bins in axis order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseMatrix {
+    // probabilities[j][i]: probability that an event of truth bin i is measured in reco bin j
+    pub probabilities: Vec<Vec<f64>>,
+    // fraction of the events of each truth bin measured in any reco bin
+    pub efficiencies: Vec<f64>,
+    // fraction of fakes in each reco bin
+    pub fake_fractions: Vec<f64>,
+    // truth spectrum including misses
+    pub truth: Vec<f64>,
+}
+
+#[derive(Debug)]
+pub struct Unfolded<H> {
+    pub hist: H,
+    pub values: Vec<f64>,
+    pub covariance: Vec<Vec<f64>>,
+}
+
+#[derive(Debug)]
+pub struct Response<H> {
+    pub hist: H,
+}
+
+impl<H: Histogram> Response<H> {
+    // `hist` has the axes (reco, truth)
+    pub fn new(hist: H) -> Result<Self> {
+        let naxes = hist.get_axes().len();
+        if naxes != 2 {
+            return Err(UnfoldError::NotTwoDimensional(naxes).into());
+        }
+        // the unfolded histograms are written with `set`
+        hist.get_bin(0).zero().set(0.0, 0.0)?;
+        Ok(Self { hist })
+    }
+
+    pub fn fill(&mut self, reco: usize, truth: usize, weight: f32) -> Result<()> {
+        self.hist.fill(&[reco, truth], weight)
+    }
+
+    // reconstructed event without a truth match
+    pub fn fill_fake(&mut self, reco: usize, weight: f32) -> Result<()> {
+        let truth = self.hist.get_axes()[1].overflow();
+        self.hist.fill(&[reco, truth], weight)
+    }
+
+    // truth event that is not reconstructed
+    pub fn fill_miss(&mut self, truth: usize, weight: f32) -> Result<()> {
+        let reco = self.hist.get_axes()[0].overflow();
+        self.hist.fill(&[reco, truth], weight)
+    }
+
+    pub fn matrix(&self) -> Result<ResponseMatrix> {
+        let axes = self.hist.get_axes();
+        let (reco_bins, truth_bins) = (axes[0].display_order(false), axes[1].display_order(false));
+        let content = |reco: usize, truth: usize| -> Result<f64> {
+            Ok(self
+                .hist
+                .get_bin(self.hist.stride_index(&[reco, truth])?)
+                .value())
+        };
+
+        let mut matched = vec![vec![0.0; truth_bins.len()]; reco_bins.len()];
+        let (mut truth, mut fakes) = (vec![0.0; truth_bins.len()], vec![0.0; reco_bins.len()]);
+        for reco in 0..axes[0].num_bins(true) {
+            let reco_pos = reco_bins.iter().position(|&bin| bin == reco);
+            for t in 0..axes[1].num_bins(true) {
+                let value = content(reco, t)?;
+                match (reco_pos, truth_bins.iter().position(|&bin| bin == t)) {
+                    (Some(j), Some(i)) => {
+                        matched[j][i] = value;
+                        truth[i] += value;
+                    }
+                    // misses
+                    (None, Some(i)) => truth[i] += value,
+                    (Some(j), None) => fakes[j] += value,
+                    (None, None) => {}
+                }
+            }
+        }
+
+        let ratio = |a: f64, b: f64| if b != 0.0 { a / b } else { 0.0 };
+        let probabilities = matched
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(truth.iter())
+                    .map(|(&m, &t)| ratio(m, t))
+                    .collect()
+            })
+            .collect::<Vec<Vec<f64>>>();
+        let efficiencies = (0..truth.len())
+            .map(|i| probabilities.iter().map(|row| row[i]).sum())
+            .collect();
+        let fake_fractions = matched
+            .iter()
+            .zip(fakes.iter())
+            .map(|(row, &f)| ratio(f, row.iter().sum::<f64>() + f))
+            .collect();
+        Ok(ResponseMatrix {
+            probabilities,
+            efficiencies,
+            fake_fractions,
+            truth,
+        })
+    }
+
+    // iterative Bayesian unfolding (D'Agostini) of the reco-level `data`,
+    // starting from the truth spectrum of the response as prior
+    pub fn bayes<D>(&self, data: &D, iterations: usize) -> Result<Unfolded<H>>
+    where
+        D: Histogram + ?Sized,
+    {
+        if iterations == 0 {
+            return Err(UnfoldError::NoIterations.into());
+        }
+        let matrix = self.matrix()?;
+        let (measured, variances) = self.signal(data, &matrix)?;
+        let (nreco, ntruth) = (measured.len(), matrix.truth.len());
+
+        // the unfolding matrix does not depend on the normalisation of the prior,
+        // the unnormalised prior keeps the derivatives consistent
+        let mut prior = matrix.truth.clone();
+        // derivatives of the unfolded values with respect to the measured values
+        let mut derivatives = vec![vec![0.0; nreco]; ntruth];
+        let mut values = vec![0.0; ntruth];
+        for _ in 0..iterations {
+            let folded: Vec<f64> = matrix
+                .probabilities
+                .iter()
+                .map(|row| row.iter().zip(prior.iter()).map(|(p, q)| p * q).sum())
+                .collect();
+            // unfolding[i][j] = P(truth i | reco j) / efficiency i
+            let unfolding: Vec<Vec<f64>> = (0..ntruth)
+                .map(|i| {
+                    (0..nreco)
+                        .map(|j| {
+                            if folded[j] > 0.0 && matrix.efficiencies[i] > 0.0 {
+                                matrix.probabilities[j][i] * prior[i]
+                                    / (folded[j] * matrix.efficiencies[i])
+                            } else {
+                                0.0
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            values = unfolding
+                .iter()
+                .map(|row| row.iter().zip(measured.iter()).map(|(m, d)| m * d).sum())
+                .collect();
+
+            // error propagation through the iterations (Adye):
+            // derivatives = unfolding + d(values)/d(prior) * previous derivatives
+            let by_prior: Vec<Vec<f64>> = (0..ntruth)
+                .map(|i| {
+                    (0..ntruth)
+                        .map(|m| {
+                            let own = if i == m && prior[i] != 0.0 {
+                                values[i] / prior[i]
+                            } else {
+                                0.0
+                            };
+                            let shared: f64 = (0..nreco)
+                                .filter(|&j| folded[j] > 0.0)
+                                .map(|j| {
+                                    unfolding[i][j] * measured[j] * matrix.probabilities[j][m]
+                                        / folded[j]
+                                })
+                                .sum();
+                            own - shared
+                        })
+                        .collect()
+                })
+                .collect();
+            derivatives = (0..ntruth)
+                .map(|i| {
+                    (0..nreco)
+                        .map(|k| {
+                            unfolding[i][k]
+                                + (0..ntruth)
+                                    .map(|m| by_prior[i][m] * derivatives[m][k])
+                                    .sum::<f64>()
+                        })
+                        .collect()
+                })
+                .collect();
+            prior = values.clone();
+        }
+
+        // V = D diag(variances) D^T
+        let covariance = (0..ntruth)
+            .map(|a| {
+                (0..ntruth)
+                    .map(|b| {
+                        (0..nreco)
+                            .map(|j| derivatives[a][j] * variances[j] * derivatives[b][j])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        self.unfolded(values, covariance)
+    }
+
+    // SVD unfolding with curvature regularization (Hoecker & Kartvelishvili) of the reco-level
+    // `data`, the regularization parameter is the square of the `k`-th singular value
+    // (larger `k`: weaker regularization)
+    pub fn svd<D>(&self, data: &D, k: usize) -> Result<Unfolded<H>>
+    where
+        D: Histogram + ?Sized,
+    {
+        let matrix = self.matrix()?;
+        let (measured, variances) = self.signal(data, &matrix)?;
+        let (nreco, ntruth) = (measured.len(), matrix.truth.len());
+        if k == 0 || k > ntruth {
+            return Err(UnfoldError::InvalidRegularization { k, nbins: ntruth }.into());
+        }
+
+        // number of events response rescaled by the data uncertainties,
+        // padded with empty rows for fewer reco than truth bins
+        let sigma: Vec<f64> = variances
+            .iter()
+            .map(|&v| if v > 0.0 { v.sqrt() } else { 1.0 })
+            .collect();
+        let mut scaled: Vec<Vec<f64>> = (0..nreco)
+            .map(|j| {
+                (0..ntruth)
+                    .map(|i| matrix.probabilities[j][i] * matrix.truth[i] / sigma[j])
+                    .collect()
+            })
+            .collect();
+        scaled.resize(nreco.max(ntruth), vec![0.0; ntruth]);
+        let mut measured: Vec<f64> = measured
+            .iter()
+            .zip(sigma.iter())
+            .map(|(d, s)| d / s)
+            .collect();
+        measured.resize(scaled.len(), 0.0);
+
+        // regularize the curvature of the ratio to the truth spectrum of the response
+        let inverse_curvature = invert(&curvature(ntruth)).ok_or(UnfoldError::SingularCurvature)?;
+        let product: Vec<Vec<f64>> = scaled
+            .iter()
+            .map(|row| {
+                (0..ntruth)
+                    .map(|b| (0..ntruth).map(|a| row[a] * inverse_curvature[a][b]).sum())
+                    .collect()
+            })
+            .collect();
+        let Svd { u, s, v } = svd(&product);
+        let tau = s[k - 1] * s[k - 1];
+        let rotated: Vec<f64> = transpose(&u)
+            .iter()
+            .map(|column| column.iter().zip(measured.iter()).map(|(u, d)| u * d).sum())
+            .collect();
+        // filter factor s / (s^2 + tau) of each component
+        let damped = |i: usize| {
+            let denominator = s[i] * s[i] + tau;
+            if denominator > 0.0 {
+                s[i] / denominator
+            } else {
+                0.0
+            }
+        };
+        // back-transformation C^-1 V of each component
+        let basis: Vec<Vec<f64>> = (0..ntruth)
+            .map(|a| {
+                (0..ntruth)
+                    .map(|i| (0..ntruth).map(|b| inverse_curvature[a][b] * v[b][i]).sum())
+                    .collect()
+            })
+            .collect();
+        let values: Vec<f64> = (0..ntruth)
+            .map(|a| {
+                let w: f64 = (0..ntruth)
+                    .map(|i| basis[a][i] * damped(i) * rotated[i])
+                    .sum();
+                w * matrix.truth[a]
+            })
+            .collect();
+        // the rescaled data have unit covariance
+        let covariance = (0..ntruth)
+            .map(|a| {
+                (0..ntruth)
+                    .map(|b| {
+                        let w: f64 = (0..ntruth)
+                            .map(|i| basis[a][i] * damped(i).powi(2) * basis[b][i])
+                            .sum();
+                        w * matrix.truth[a] * matrix.truth[b]
+                    })
+                    .collect()
+            })
+            .collect();
+        self.unfolded(values, covariance)
+    }
+
+    // regular bins of the reco-level `data` without fakes and their variances
+    fn signal<D>(&self, data: &D, matrix: &ResponseMatrix) -> Result<(Vec<f64>, Vec<f64>)>
+    where
+        D: Histogram + ?Sized,
+    {
+        check_equal_axes(&self.hist.get_axes()[..1], data.get_axes())?;
+        let purity: Vec<f64> = matrix.fake_fractions.iter().map(|f| 1.0 - f).collect();
+        Ok((
+            data.values(false)
+                .iter()
+                .zip(purity.iter())
+                .map(|(d, p)| d * p)
+                .collect(),
+            data.variances(false)
+                .iter()
+                .zip(purity.iter())
+                .map(|(v, p)| v * p * p)
+                .collect(),
+        ))
+    }
+
+    fn unfolded(&self, values: Vec<f64>, covariance: Vec<Vec<f64>>) -> Result<Unfolded<H>> {
+        let truth_axis = self.hist.get_axes()[1].clone();
+        let bins = truth_axis.display_order(false);
+        let mut hist = self.hist.empty_like(vec![truth_axis]);
+        for (i, (&idx, &value)) in bins.iter().zip(values.iter()).enumerate() {
+            let mut bin = self.hist.get_bin(0).zero();
+            bin.set(value, covariance[i][i])?;
+            hist.update_bin(idx, &mut |b| *b = bin.clone());
+        }
+        Ok(Unfolded {
+            hist,
+            values,
+            covariance,
+        })
+    }
+}
+
+// second-derivative matrix with a small diagonal regulator
+fn curvature(n: usize) -> Vec<Vec<f64>> {
+    (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| {
+                    let value = match i.abs_diff(j) {
+                        0 if n == 1 => 0.0,
+                        0 if i == 0 || i == n - 1 => -1.0,
+                        0 => -2.0,
+                        1 => 1.0,
+                        _ => 0.0,
+                    };
+                    if i == j {
+                        value + CURVATURE_REGULATOR
+                    } else {
+                        value
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}